@@ -0,0 +1,187 @@
+use std::path::PathBuf;
+
+use clap::{ArgGroup, Parser};
+use thiserror::Error;
+
+use crate::village::Village;
+
+/// the command-line arguments for the game. every villager count defaults to the
+/// (semi-arbitrary) setup the game has always used
+#[derive(Parser, Debug, PartialEq, Eq)]
+#[command(about = "A murder mystery game played with programmable robots")]
+#[command(group(ArgGroup::new("program").args(["code", "stdin"]).multiple(true)))]
+pub struct Args {
+    /// the number of normal villagers
+    #[arg(long, default_value_t = 6)]
+    pub normal: u8,
+
+    /// the number of strong villagers
+    #[arg(long, default_value_t = 2)]
+    pub strong: u8,
+
+    /// the number of attacks each strong villager survives
+    #[arg(long, default_value_t = 1)]
+    pub strong_lives: u8,
+
+    /// the number of afraid villagers
+    #[arg(long, default_value_t = 2)]
+    pub afraid: u8,
+
+    /// the number of doctors
+    #[arg(long, default_value_t = 0)]
+    pub doctors: u8,
+
+    /// the number of murderers
+    #[arg(long, default_value_t = 2)]
+    pub murderers: u8,
+
+    /// the number of days the murderers have to be stopped in (unlimited if not given)
+    #[arg(long)]
+    pub day_limit: Option<u32>,
+
+    /// the seed for all of the game's randomness, for replaying a game (random if not given)
+    #[arg(long)]
+    pub seed: Option<u64>,
+
+    /// run the mini code in this file for a single day without any prompts, then exit
+    #[arg(long, requires = "start")]
+    pub code: Option<PathBuf>,
+
+    /// like --code, but reads the mini code from stdin. --start is required here too, and
+    /// --code takes precedence if both are given
+    #[arg(long, requires = "start")]
+    pub stdin: bool,
+
+    /// the starting location of the mini run with --code or --stdin (required with either)
+    #[arg(long, requires = "program")]
+    pub start: Option<u8>,
+
+    /// run minis one instruction at a time, showing the mini's state after each
+    /// instruction and waiting for enter to be pressed before the next one
+    #[arg(long)]
+    pub debug: bool,
+
+    /// after each day, write the mini's log to this file (as json if the file
+    /// name ends in .json, otherwise as csv)
+    #[arg(long)]
+    pub log_out: Option<PathBuf>,
+
+    /// print a summary of the game as json instead of describing what happens
+    /// (the game still asks for mini code and starting locations, unless given --code)
+    #[cfg(feature = "serde")]
+    #[arg(long)]
+    pub json: bool,
+}
+
+impl Args {
+    /// makes sure the arguments describe a village a game can be played in
+    pub fn validate(&self) -> Result<(), CliError> {
+        let non_murderers =
+            self.normal as u16 + self.strong as u16 + self.afraid as u16 + self.doctors as u16;
+        if self.murderers == 0 {
+            return Err(CliError::NoMurderers);
+        }
+        if non_murderers == 0 {
+            return Err(CliError::NoVillagers);
+        }
+
+        // villagers are labeled from 1, so there can't be more than u8::MAX of them
+        let total = non_murderers + self.murderers as u16;
+        if total > u8::MAX as u16 {
+            return Err(CliError::TooManyVillagers(total));
+        }
+
+        Ok(())
+    }
+
+    /// constructs the village described by the arguments using the provided seed.
+    /// minis get their randomness from the village, so this decides everything random
+    pub fn village(&self, seed: u64) -> Village {
+        let mut village = Village::new_seeded(
+            seed,
+            self.normal,
+            self.strong,
+            self.strong_lives,
+            self.afraid,
+            self.doctors,
+            self.murderers,
+        );
+        village.set_day_limit(self.day_limit);
+        village
+    }
+}
+
+/// represents anything that can be wrong with the command-line arguments
+#[derive(Error, Debug, PartialEq, Eq)]
+pub enum CliError {
+    #[error("the village needs at least one murderer")]
+    NoMurderers,
+
+    #[error("the village needs at least one villager who isn't a murderer")]
+    NoVillagers,
+
+    /// the u16 is the number of villagers asked for
+    #[error("the village can't have more than 255 villagers (asked for {0})")]
+    TooManyVillagers(u16),
+}
+
+#[cfg(test)]
+mod test {
+    use clap::Parser;
+
+    use crate::cli::{Args, CliError};
+
+    #[test]
+    fn parse_args() {
+        let args =
+            Args::try_parse_from(["board-game", "--normal", "3", "--murderers", "1"]).unwrap();
+        assert_eq!((args.normal, args.murderers), (3, 1));
+        // anything missing falls back to the default setup
+        assert_eq!((args.strong, args.afraid, args.doctors), (2, 2, 0));
+        assert_eq!(args.day_limit, None);
+        assert_eq!(args.validate(), Ok(()));
+
+        let args = Args::try_parse_from(["board-game", "--murderers", "0"]).unwrap();
+        assert_eq!(args.validate(), Err(CliError::NoMurderers));
+
+        let args = Args::try_parse_from([
+            "board-game",
+            "--normal",
+            "0",
+            "--strong",
+            "0",
+            "--afraid",
+            "0",
+        ])
+        .unwrap();
+        assert_eq!(args.validate(), Err(CliError::NoVillagers));
+
+        assert!(Args::try_parse_from(["board-game", "--normal", "-1"]).is_err());
+        assert!(Args::try_parse_from(["board-game", "--wizards", "1"]).is_err());
+    }
+
+    #[test]
+    fn seed() {
+        let args = Args::try_parse_from(["board-game", "--seed", "1234"]).unwrap();
+        assert_eq!(args.seed, Some(1234));
+        assert_eq!(args.village(1234).layout(), args.village(1234).layout());
+
+        assert_eq!(Args::try_parse_from(["board-game"]).unwrap().seed, None);
+    }
+
+    #[test]
+    fn headless() {
+        let args =
+            Args::try_parse_from(["board-game", "--code", "code.mm", "--start", "3"]).unwrap();
+        assert_eq!(args.code, Some("code.mm".into()));
+        assert_eq!(args.start, Some(3));
+
+        // neither works without the other
+        assert!(Args::try_parse_from(["board-game", "--code", "code.mm"]).is_err());
+        assert!(Args::try_parse_from(["board-game", "--start", "3"]).is_err());
+
+        let args = Args::try_parse_from(["board-game", "--stdin", "--start", "3"]).unwrap();
+        assert!(args.stdin);
+        assert!(Args::try_parse_from(["board-game", "--stdin"]).is_err());
+    }
+}
@@ -1,113 +1,148 @@
-use std::{io::Write, path::PathBuf, str::FromStr};
+use std::{
+    io::Write,
+    path::{Path, PathBuf},
+    str::FromStr,
+};
 
-use mini::Mini;
-use parser::parse_instructions;
+use clap::Parser;
+use cli::Args;
+use mini::{EventLog, Instructions, Mini, MiniConfig, MiniStatus};
+use parser::{MMParsingError, parse_instructions, parse_str};
 use rfd::FileDialog;
-use village::{Village, VillageStatus};
+use village::{NightReport, Village, VillageStatus};
 
+mod cli;
 mod mini;
 mod parser;
+#[cfg(feature = "serde")]
+mod summary;
 mod village;
 
+/// the most instructions a player's mini can run. this is far more than any
+/// reasonable program needs, but small enough to finish quickly
+const INSTRUCTION_BUDGET: u64 = 1_000_000;
+
 fn main() {
-    // since I've been pretty pressed between this and assignments, I haven't been
-    // able to determine the most enjoyable village setup, so for now it's just these
-    // semi-arbitrary values
-    let mut village = Village::new(6, 2, 2, 2);
+    // the village setup comes from the command line (see cli.rs for the defaults)
+    let args = Args::parse();
+    if let Err(error) = args.validate() {
+        eprintln!("error: {}", error);
+        std::process::exit(1);
+    }
+    // with --json, a summary is printed at the end instead of describing things as they happen
+    #[cfg(feature = "serde")]
+    let verbose = !args.json;
+    #[cfg(not(feature = "serde"))]
+    let verbose = true;
 
-    loop {
-        let instructions;
-        let starting_location;
-
-        // get instructions for the mini
-        loop {
-            let file = match FileDialog::new()
-                .set_title("Select mini code")
-                .add_filter("mm code", &["mm", "txt"])
-                .set_directory("/")
-                .set_can_create_directories(true)
-                .pick_file()
-            {
-                Some(file) => file,
-
-                // if the file dialog fails (like it does on NixOS unfortunately), just
-                // prompt from the command-line
-                None => {
-                    print!("Select file containing mini code: ");
-                    std::io::stdout().flush().expect("failed to flush stdout");
-                    let mut buffer = String::new();
-                    std::io::stdin()
-                        .read_line(&mut buffer)
-                        .expect("failed to read stdin");
-
-                    // if given an invalid path (such as an empty string), just ask again
-                    match PathBuf::from_str(buffer.trim()) {
-                        Ok(path) => path,
-                        Err(_) => continue,
-                    }
-                }
-            };
+    // print the seed so the game can be replayed with --seed
+    let seed = args.seed.unwrap_or_else(rand::random);
+    if verbose {
+        println!("Seed: {}\n", seed);
+    }
+    let mut village = args.village(seed);
+    let mut mini_logs = Vec::new();
 
-            // if the we successfully parse instructions, move on.
-            // otherwise, prompt the user again
-            match parse_instructions(file) {
-                Ok(ins) => {
-                    instructions = ins;
-                    break;
-                }
-                Err(error) => println!("please try again: {}", error),
+    // with --code or --stdin, run a single day without prompting for anything
+    if let Some(starting_location) = args.start {
+        let instructions = match &args.code {
+            Some(code) => parse_instructions(code.clone()),
+            None => read_stdin_code(),
+        };
+        let instructions = match instructions {
+            Ok(instructions) => instructions,
+            Err(error) => {
+                eprintln!("error: {}", error);
+                std::process::exit(1);
             }
+        };
+        if !village.villager_exists(starting_location) {
+            eprintln!("error: there is no villager at {}", starting_location);
+            std::process::exit(1);
         }
 
-        // get starting location
-        loop {
-            print!("Select starting location: ");
-            std::io::stdout().flush().expect("failed to flush stdout");
-            let mut buffer = String::new();
-            std::io::stdin()
-                .read_line(&mut buffer)
-                .expect("failed to read stdin");
-
-            // if we were given a valid u8, continue. otherwise, ask again
-            match buffer.trim().parse::<u8>() {
-                Ok(location) => {
-                    if village.villager_exists(location) {
-                        starting_location = location;
-                        break;
-                    } else {
-                        println!("there is no villager at that location")
-                    }
-                }
-                Err(e) => println!("that's not a valid number: {}", e),
-            }
+        let minis = run_minis(
+            &mut village,
+            vec![(starting_location, instructions)],
+            args.debug,
+            verbose,
+        );
+        mini_logs.extend(end_day(
+            &mut village,
+            &minis,
+            args.log_out.as_deref(),
+            verbose,
+        ));
+        match verbose {
+            true => println!("\nVillage status: {:?}", village.status()),
+            false => print_summary(seed, &village, mini_logs),
         }
+        return;
+    }
 
-        // run the mini and output the log
-        let mut mini = Mini::new(starting_location, instructions, &village);
-        mini.run_until_completion(&mut village);
-        println!("\nMini log:");
-        mini.log().iter().for_each(|log| println!("{:?}", log));
+    loop {
+        if verbose {
+            println!("{}\n", village.daily_briefing());
+        }
 
-        // run the village night and handle winning/losing
-        village.run_night();
+        // each mini runs as soon as it's given, so the next one sees what it did. once
+        // the game is over (say, because a mini killed the last murderer), the day ends
+        let mut minis = Vec::new();
+        let mut instructions = Some(prompt_instructions(verbose));
+        while let Some(code) = instructions {
+            let starting_location = prompt_starting_location(&village, verbose);
+            minis.extend(run_minis(
+                &mut village,
+                vec![(starting_location, code)],
+                args.debug,
+                verbose,
+            ));
+            instructions = match village.status() {
+                VillageStatus::Running => prompt_more_instructions(verbose),
+                _ => None,
+            };
+        }
+        mini_logs.extend(end_day(
+            &mut village,
+            &minis,
+            args.log_out.as_deref(),
+            verbose,
+        ));
+
+        // handle winning/losing
         if village.status() != VillageStatus::Running {
             break;
         }
+        if !verbose {
+            continue;
+        }
 
         // print information and continue to next iteration
-        print!("Day complete. Press enter to continue... ");
-        std::io::stdout().flush().expect("failed to flush stdout");
-        let mut buffer = String::new();
-        std::io::stdin()
-            .read_line(&mut buffer)
-            .expect("failed to read stdin");
+        ask("Day complete. Press enter to continue... ", verbose);
         println!();
     }
 
+    if !verbose {
+        print_summary(seed, &village, mini_logs);
+        return;
+    }
+
     // print game overview
     match village.status() {
-        VillageStatus::MurdersWon => println!("\nYou lose! All the villagers have died."),
+        VillageStatus::MurdersWon => {
+            println!("\nYou lose! All the villagers have died.");
+            println!("You survived {} days.", village.day());
+        }
         VillageStatus::VillagersWon => println!("\nYou win! All the murderers have died."),
+        VillageStatus::TimeExpired => {
+            println!(
+                "\nYou lose! The murderers are still alive after {} days.",
+                village.day()
+            )
+        }
+        VillageStatus::Stalemate => {
+            println!("\nIt's a draw! The murderers can't reach anyone, but they're still alive.")
+        }
         VillageStatus::Running => unreachable!(),
     }
 
@@ -115,15 +150,333 @@ fn main() {
     let mut layout = village.layout();
     layout.sort_by_key(|villager| villager.label());
     layout.iter().for_each(|villager| {
-        println!(
-            "{}: {}",
-            villager.label(),
-            match villager.kind() {
-                village::VillagerType::Normal => "normal villager",
-                village::VillagerType::Strong(_) => "strong villager",
-                village::VillagerType::Afraid => "afraid villager",
-                village::VillagerType::Murderer => "murderer",
-            }
-        )
+        let label = match villager.name() {
+            Some(name) => format!("{} ({})", villager.label(), name),
+            None => villager.label().to_string(),
+        };
+        println!("{}: {}", label, villager.kind())
     });
 }
+
+/// reads and parses all of stdin as mini code (for --stdin)
+fn read_stdin_code() -> Result<Instructions, MMParsingError> {
+    let source = std::io::read_to_string(std::io::stdin())
+        .map_err(|error| MMParsingError::BadStdin(error.to_string()))?;
+    if source.trim().is_empty() {
+        return Err(MMParsingError::NoCode);
+    }
+
+    parse_str(&source)
+}
+
+/// shows the player a message. without verbose output, stdout is kept for the json
+/// summary, so messages go to stderr instead
+fn tell(message: &str, verbose: bool) {
+    match verbose {
+        true => println!("{}", message),
+        false => eprintln!("{}", message),
+    }
+}
+
+/// shows the player a prompt (on the same stream as `tell`) and reads their reply
+fn ask(prompt: &str, verbose: bool) -> String {
+    match verbose {
+        true => {
+            print!("{}", prompt);
+            std::io::stdout().flush().expect("failed to flush stdout");
+        }
+        false => {
+            eprint!("{}", prompt);
+            std::io::stderr().flush().expect("failed to flush stderr");
+        }
+    }
+    let mut buffer = String::new();
+    std::io::stdin()
+        .read_line(&mut buffer)
+        .expect("failed to read stdin");
+    buffer
+}
+
+/// asks the player for a file of mini code until they give one that parses
+fn prompt_instructions(verbose: bool) -> Instructions {
+    loop {
+        let file = match FileDialog::new()
+            .set_title("Select mini code")
+            .add_filter("mm code", &["mm", "txt"])
+            .set_directory("/")
+            .set_can_create_directories(true)
+            .pick_file()
+        {
+            Some(file) => file,
+
+            // if the file dialog fails (like it does on NixOS unfortunately), just
+            // prompt from the command-line
+            None => {
+                let buffer = ask("Select file containing mini code: ", verbose);
+
+                // if given an invalid path (such as an empty string), just ask again
+                match PathBuf::from_str(buffer.trim()) {
+                    Ok(path) => path,
+                    Err(_) => continue,
+                }
+            }
+        };
+
+        // if the we successfully parse instructions, move on.
+        // otherwise, prompt the user again
+        match parse_instructions(file) {
+            Ok(instructions) => return instructions,
+            Err(error) => tell(&format!("please try again: {}", error), verbose),
+        }
+    }
+}
+
+/// asks the player for another file of mini code until they give one that parses,
+/// or None if they enter an empty line instead
+fn prompt_more_instructions(verbose: bool) -> Option<Instructions> {
+    loop {
+        let buffer = ask(
+            "Select file containing another mini's code (or press enter to end the day): ",
+            verbose,
+        );
+
+        if buffer.trim().is_empty() {
+            return None;
+        }
+        match parse_instructions(PathBuf::from(buffer.trim())) {
+            Ok(instructions) => return Some(instructions),
+            Err(error) => tell(&format!("please try again: {}", error), verbose),
+        }
+    }
+}
+
+/// asks the player where to start their mini until they give an existing villager
+fn prompt_starting_location(village: &Village, verbose: bool) -> u8 {
+    loop {
+        let buffer = ask("Select starting location: ", verbose);
+
+        // if we were given a valid u8, continue. otherwise, ask again
+        match buffer.trim().parse::<u8>() {
+            Ok(location) => {
+                if village.villager_exists(location) {
+                    return location;
+                } else {
+                    tell("there is no villager at that location", verbose)
+                }
+            }
+            Err(e) => tell(&format!("that's not a valid number: {}", e), verbose),
+        }
+    }
+}
+
+/// prints the game's summary as json (for --json)
+#[cfg(feature = "serde")]
+fn print_summary(seed: u64, village: &Village, mini_logs: Vec<EventLog>) {
+    println!(
+        "{}",
+        summary::GameSummary::new(seed, village, mini_logs).to_json()
+    );
+}
+
+/// without serde, there's no --json, so summaries are never printed
+#[cfg(not(feature = "serde"))]
+fn print_summary(_seed: u64, _village: &Village, _mini_logs: Vec<EventLog>) {
+    unreachable!("summaries are only printed with --json")
+}
+
+/// the mini's log as it should be written to the provided file (for --log-out)
+#[cfg(feature = "serde")]
+fn export_log(mini: &Mini, path: &Path) -> String {
+    match path
+        .extension()
+        .is_some_and(|extension| extension == "json")
+    {
+        true => mini.log_to_json(),
+        false => mini.log_to_csv(),
+    }
+}
+
+/// without serde, logs can only be written as csv
+#[cfg(not(feature = "serde"))]
+fn export_log(mini: &Mini, _path: &Path) -> String {
+    mini.log_to_csv()
+}
+
+/// runs each of the player's minis (given as starting locations and code) one after
+/// another, so every mini sees what the ones before it did to the village. when
+/// debugging, each mini is stepped through one instruction at a time
+fn run_minis(
+    village: &mut Village,
+    programs: Vec<(u8, Instructions)>,
+    debug: bool,
+    verbose: bool,
+) -> Vec<Mini> {
+    let config = MiniConfig {
+        instruction_budget: Some(INSTRUCTION_BUDGET),
+        ..Default::default()
+    };
+
+    programs
+        .into_iter()
+        .map(|(starting_location, instructions)| {
+            let mut mini = Mini::new_with_config(starting_location, instructions, village, config)
+                .expect("the starting location was checked to exist");
+            match debug {
+                true => debug_mini(&mut mini, village, verbose),
+                false => mini.run_until_completion(village),
+            }
+            mini
+        })
+        .collect()
+}
+
+/// runs a mini one instruction at a time (for --debug), printing its state after
+/// each instruction and waiting for the player to press enter
+fn debug_mini(mini: &mut Mini, village: &mut Village, verbose: bool) {
+    let mut steps = 0;
+    while mini.status() == MiniStatus::Running {
+        mini.step(village);
+        steps += 1;
+        let state = format!(
+            "step {}: register {}, location {}, {}, {} instructions left",
+            steps,
+            mini.register(),
+            mini.location(),
+            mini.status(),
+            mini.stack_len()
+        );
+
+        // once the mini has stopped, there's nothing left to wait for
+        if mini.status() != MiniStatus::Running {
+            tell(&format!("{}. The mini has stopped.", state), verbose);
+            break;
+        }
+        ask(&format!("{} (press enter to continue) ", state), verbose);
+    }
+}
+
+/// the file the log of the mini at the provided index is written to. the first mini's
+/// log goes to the path given with --log-out, and later ones get a number added, like
+/// `log-2.csv`
+fn log_path(path: &Path, index: usize) -> PathBuf {
+    if index == 0 {
+        return path.to_path_buf();
+    }
+
+    let stem = path.file_stem().unwrap_or_default().to_string_lossy();
+    let file_name = match path.extension() {
+        Some(extension) => format!("{}-{}.{}", stem, index + 1, extension.to_string_lossy()),
+        None => format!("{}-{}", stem, index + 1),
+    };
+    path.with_file_name(file_name)
+}
+
+/// runs the night after the day's minis have run, describing what happened if verbose.
+/// if given a path, the minis' logs are also written there. returns the minis' logs
+fn end_day(
+    village: &mut Village,
+    minis: &[Mini],
+    log_out: Option<&Path>,
+    verbose: bool,
+) -> Vec<EventLog> {
+    if let Some(path) = log_out {
+        minis.iter().enumerate().for_each(|(index, mini)| {
+            let path = log_path(path, index);
+            if let Err(error) = std::fs::write(&path, export_log(mini, &path)) {
+                eprintln!(
+                    "error: couldn't write the mini's log to `{}`: {}",
+                    path.display(),
+                    error
+                );
+            }
+        });
+    }
+
+    // run the village night
+    let report = village.run_night();
+
+    if verbose {
+        minis.iter().enumerate().for_each(|(index, mini)| {
+            match minis.len() {
+                1 => println!("\nMini log:"),
+                _ => println!("\nMini {} log:", index + 1),
+            }
+            mini.log().iter().for_each(|log| println!("{}", log));
+            match mini.status() {
+                MiniStatus::Running | MiniStatus::Done => (),
+                status => println!("The mini was {}.", status),
+            }
+        });
+        print_night_report(&report);
+    }
+
+    minis.iter().map(|mini| mini.log().clone()).collect()
+}
+
+fn print_night_report(report: &NightReport) {
+    println!("\nDuring the night:");
+    if report.poison_deaths.is_empty() && report.attacks.is_empty() && report.revivals.is_empty() {
+        println!("Nothing happened.");
+    }
+    report
+        .poison_deaths
+        .iter()
+        .for_each(|label| println!("Villager {} died of poison.", label));
+    // attackers (and who was poisoned tonight) aren't shown, since that would give
+    // the murderers and poisoners away
+    report
+        .attacks
+        .iter()
+        .for_each(|attack| match attack.resisted {
+            true => println!("Villager {} survived an attack.", attack.victim),
+            false => println!("Villager {} was killed.", attack.victim),
+        });
+    report
+        .revivals
+        .iter()
+        .for_each(|label| println!("Villager {} was revived.", label));
+}
+
+#[cfg(test)]
+mod test {
+    use std::path::Path;
+
+    use crate::{
+        log_path,
+        mini::Event,
+        parser::parse_str,
+        run_minis,
+        village::{Village, Villager, VillagerType},
+    };
+
+    #[test]
+    fn minis_share_the_village() {
+        let mut village = Village::new_deterministic(vec![
+            Villager::new(VillagerType::Normal, 1),
+            Villager::new(VillagerType::Normal, 2),
+            Villager::new(VillagerType::Murderer, 3),
+        ]);
+
+        // the second mini should see the villager the first one killed
+        let programs = vec![
+            (1, parse_str("detonate 2").unwrap()),
+            (1, parse_str("if dead 2 { post flare }").unwrap()),
+        ];
+        let minis = run_minis(&mut village, programs, false, true);
+
+        assert!(village.dead_villager(2).is_some());
+        assert_eq!(
+            minis[0].log(),
+            &vec![Event::Visited(1), Event::Detonated(2)]
+        );
+        assert!(minis[1].log().contains(&Event::PostedFlare));
+    }
+
+    #[test]
+    fn log_paths() {
+        let path = Path::new("logs/day.csv");
+        assert_eq!(log_path(path, 0), Path::new("logs/day.csv"));
+        assert_eq!(log_path(path, 1), Path::new("logs/day-2.csv"));
+        assert_eq!(log_path(Path::new("day"), 2), Path::new("day-3"));
+    }
+}
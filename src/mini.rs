@@ -1,60 +1,521 @@
-use crate::village::{Village, VillagerType};
+use std::{fmt, rc::Rc};
+
+use thiserror::Error;
+
+use crate::village::{Direction, Village, VillagerType, VisitSafety};
 
 /// an action a mini can take
 #[derive(Debug, PartialEq, Eq, Clone, Copy)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub enum Action {
     PostRegister,
     PostFlare,
+    /// posts a flare only if no flare has been posted yet
+    PostFlareOnce,
+    /// posts how far away the nearest living villager in a direction is
+    PostNeighborDistance(Direction),
     Detonate,
+    /// detonates targeting the provided label instead of the register
+    DetonateAt(u8),
+    /// only detonates if the register holds the label of a living villager
+    DetonateIfValid,
     Visit,
+    /// marks a section of the program in the log
+    Checkpoint(u8),
+    /// posts the xor of every register value posted so far
+    PostChecksum,
+    /// visits the living villager whose position (counting up from zero, in order
+    /// of label) is in the register. the mini is lost if there's no such villager
+    VisitNth,
+    /// sets the register to the mini's location
+    ReadLocation,
+    /// sets the register to the number of living villagers
+    CountLiving,
+    /// sets the register to a random value in `0..=N`, where N is the operand. the
+    /// randomness comes from the village, so programs using this are only deterministic
+    /// if the village is seeded
+    Randomize(u8),
+    /// visits the nearest living villager with a higher/lower label than the mini's
+    /// location. the mini is lost if there's no such villager
+    VisitUp,
+    VisitDown,
+    /// visits the location the mini was at before its most recent move, if there was one
+    Return,
+}
+
+/// one of a mini's registers. register a is the working register, which everything
+/// not explicitly given a register uses; register b is just for holding onto values
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum Register {
+    A,
+    B,
 }
 
 /// an operation on a mini's register
 #[derive(Debug, PartialEq, Eq, Clone, Copy)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub enum Operation {
-    Increment,
-    Decrement,
-    SetValue(u8),
+    Increment(Register),
+    Decrement(Register),
+    SetValue(Register, u8),
+    /// bit rotations are total, so these can never destroy the mini
+    RotateLeft(u8),
+    RotateRight(u8),
+    /// sets the register to the number of iterations remaining in the innermost loop
+    LoadLoopCounter,
+    /// sets the register to the distance between it and the value. this can never
+    /// destroy the mini, since the distance always fits in the register
+    AbsDiff(u8),
+    /// like increment and decrement, but by the provided amount. these
+    /// destroy the mini on overflow and underflow
+    AddImmediate(u8),
+    SubImmediate(u8),
+    /// multiplication destroys the mini on overflow, while dividing
+    /// (or taking the remainder) by zero destroys it
+    MultiplyBy(u8),
+    DivideBy(u8),
+    ModuloBy(u8),
+    /// exchanges the values of the register and the location without visiting anyone
+    SwapRegisterLocation,
+    /// exchanges the values of registers a and b
+    SwapRegisters,
+    /// saves a copy of the register to the top of the register stack
+    PushRegister,
+    /// sets the register to the value taken off the top of the register stack.
+    /// popping from an empty stack destroys the mini
+    PopRegister,
 }
 
 /// a conditional
 #[derive(Debug, PartialEq, Eq, Clone, Copy)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub enum Condition {
     VillagerIsAlive,
     VillagerIsDead,
     RegisterEq(u8),
+    RegisterGreater(u8),
+    RegisterLess(u8),
+    RegisterEven,
+    RegisterOdd,
+    /// whether the villager whose label is in the register is dead
+    RegisterTargetDead,
+    /// whether the villager with the provided label is alive/dead. if there's no
+    /// such villager, neither this nor its else block is run
+    LabelAlive(u8),
+    LabelDead(u8),
+    /// whether the mini's log has a flare in it. this only looks at the mini itself,
+    /// so it works anywhere
+    HasPostedFlare,
 }
 
 /// any instruction a mini can run
 #[derive(Debug, PartialEq, Eq, Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub enum Instruction {
     Action(Action),
     Operation(Operation),
-    Condition(Condition, Instructions),
+    Condition(Condition, Block),
+    /// like a condition, but the second block is run if the condition doesn't hold.
+    /// if the condition relies on a disabled sensor, neither block is run
+    ConditionElse(Condition, Block, Block),
     /// for infinite loop protection, decrement u8 each iteration; if it hits zero, break.
-    Repeat(u8, Instructions),
+    Repeat(u8, Block),
+    /// like a repeat, but a labeled break can leave it from inside nested loops
+    LabeledRepeat(String, u8, Block),
+    /// repeats as many times as the register's value when the loop is started
+    RepeatRegister(Block),
     Break,
+    /// breaks only if the condition holds
+    BreakIf(Condition),
+    /// leaves every loop up to and including the labeled repeat with this label
+    /// (finishing the program if it isn't inside one)
+    BreakTo(String),
+    /// skips the rest of the innermost loop's current iteration
+    Continue,
+    /// does nothing (but still counts as an instruction)
+    Noop,
 }
 
 pub type Instructions = Vec<Instruction>;
 
+/// the instructions nested inside another instruction. these are shared, so pushing
+/// a block's instructions to the stack (which happens every iteration of a repeat)
+/// only copies the block's top level
+pub type Block = Rc<Instructions>;
+
+impl fmt::Display for Action {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Action::PostRegister => write!(f, "post register"),
+            Action::PostFlare => write!(f, "post flare"),
+            Action::PostFlareOnce => write!(f, "post flare once"),
+            Action::PostNeighborDistance(Direction::Next) => write!(f, "post dist next"),
+            Action::PostNeighborDistance(Direction::Prev) => write!(f, "post dist prev"),
+            Action::Detonate => write!(f, "detonate"),
+            Action::DetonateAt(target) => write!(f, "detonate {}", target),
+            Action::DetonateIfValid => write!(f, "detonate if valid"),
+            Action::Visit => write!(f, "visit"),
+            Action::Checkpoint(label) => write!(f, "checkpoint {}", label),
+            Action::PostChecksum => write!(f, "post checksum"),
+            Action::VisitNth => write!(f, "visit nth"),
+            Action::ReadLocation => write!(f, "whereami"),
+            Action::CountLiving => write!(f, "count"),
+            Action::Randomize(max) => write!(f, "rand {}", max),
+            Action::VisitUp => write!(f, "up"),
+            Action::VisitDown => write!(f, "down"),
+            Action::Return => write!(f, "back"),
+        }
+    }
+}
+
+impl fmt::Display for Operation {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Operation::Increment(Register::A) => write!(f, "incr"),
+            Operation::Increment(Register::B) => write!(f, "incr b"),
+            Operation::Decrement(Register::A) => write!(f, "decr"),
+            Operation::Decrement(Register::B) => write!(f, "decr b"),
+            Operation::SetValue(Register::A, value) => write!(f, "set {}", value),
+            Operation::SetValue(Register::B, value) => write!(f, "set b {}", value),
+            Operation::RotateLeft(amount) => write!(f, "rotl {}", amount),
+            Operation::RotateRight(amount) => write!(f, "rotr {}", amount),
+            Operation::LoadLoopCounter => write!(f, "load loop"),
+            Operation::AbsDiff(value) => write!(f, "absdiff {}", value),
+            Operation::AddImmediate(value) => write!(f, "add {}", value),
+            Operation::SubImmediate(value) => write!(f, "sub {}", value),
+            Operation::MultiplyBy(value) => write!(f, "mul {}", value),
+            Operation::DivideBy(value) => write!(f, "div {}", value),
+            Operation::ModuloBy(value) => write!(f, "mod {}", value),
+            Operation::SwapRegisterLocation => write!(f, "swap loc"),
+            Operation::SwapRegisters => write!(f, "swap"),
+            Operation::PushRegister => write!(f, "push"),
+            Operation::PopRegister => write!(f, "pop"),
+        }
+    }
+}
+
+impl fmt::Display for Condition {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Condition::VillagerIsAlive => write!(f, "alive"),
+            Condition::VillagerIsDead => write!(f, "dead"),
+            Condition::RegisterEq(value) => write!(f, "eq {}", value),
+            Condition::RegisterGreater(value) => write!(f, "gt {}", value),
+            Condition::RegisterLess(value) => write!(f, "lt {}", value),
+            Condition::RegisterEven => write!(f, "even"),
+            Condition::RegisterOdd => write!(f, "odd"),
+            Condition::RegisterTargetDead => write!(f, "target_dead"),
+            Condition::LabelAlive(label) => write!(f, "alive {}", label),
+            Condition::LabelDead(label) => write!(f, "dead {}", label),
+            Condition::HasPostedFlare => write!(f, "flared"),
+        }
+    }
+}
+
+/// writes a block of instructions on a single line. blocks are stored in stack
+/// order, so they're written back to front to match the source they came from
+fn fmt_block(f: &mut fmt::Formatter<'_>, instructions: &Instructions) -> fmt::Result {
+    if instructions.is_empty() {
+        return write!(f, "{{}}");
+    }
+
+    write!(f, "{{")?;
+    for instruction in instructions.iter().rev() {
+        write!(f, " {}", instruction)?;
+    }
+    write!(f, " }}")
+}
+
+/// renders an instruction the way it would be written in mm code
+impl fmt::Display for Instruction {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Instruction::Action(action) => write!(f, "{}", action),
+            Instruction::Operation(operation) => write!(f, "{}", operation),
+            Instruction::Condition(condition, instructions) => {
+                write!(f, "if {} ", condition)?;
+                fmt_block(f, instructions)
+            }
+            Instruction::ConditionElse(condition, instructions, else_instructions) => {
+                write!(f, "if {} ", condition)?;
+                fmt_block(f, instructions)?;
+                write!(f, " else ")?;
+                fmt_block(f, else_instructions)
+            }
+            Instruction::Repeat(iterations, instructions) => {
+                // the default iteration count isn't written in mm code
+                match *iterations {
+                    u8::MAX => write!(f, "repeat ")?,
+                    iterations => write!(f, "repeat {} ", iterations)?,
+                }
+                fmt_block(f, instructions)
+            }
+            Instruction::LabeledRepeat(label, iterations, instructions) => {
+                match *iterations {
+                    u8::MAX => write!(f, "repeat '{} ", label)?,
+                    iterations => write!(f, "repeat {} '{} ", iterations, label)?,
+                }
+                fmt_block(f, instructions)
+            }
+            Instruction::RepeatRegister(instructions) => {
+                write!(f, "repeat register ")?;
+                fmt_block(f, instructions)
+            }
+            Instruction::Break => write!(f, "break"),
+            Instruction::BreakIf(condition) => write!(f, "break if {}", condition),
+            Instruction::BreakTo(label) => write!(f, "break '{}", label),
+            Instruction::Continue => write!(f, "continue"),
+            Instruction::Noop => write!(f, "pass"),
+        }
+    }
+}
+
+/// a conservative upper bound on how many instructions a mini could run while running the
+/// provided instructions, assuming every conditional block is taken. a repeat runs once
+/// per iteration plus once more when it runs out, so `Repeat(n, body)` contributes
+/// `n * (max_steps(body) + 1) + 1`. repeats with the default iteration count (u8::MAX,
+/// which is really just infinite loop protection) are treated as unbounded, giving None.
+/// repeating by the register is bounded by the largest value the register could hold
+#[allow(dead_code)]
+pub fn max_steps(instructions: &Instructions) -> Option<u64> {
+    let repeat_steps = |iterations: u8, block: &Instructions| {
+        (iterations as u64)
+            .checked_mul(max_steps(block)?.checked_add(1)?)?
+            .checked_add(1)
+    };
+
+    instructions.iter().try_fold(0u64, |total, instruction| {
+        let steps = match instruction {
+            Instruction::Condition(_, block) => max_steps(block)?.checked_add(1)?,
+            Instruction::ConditionElse(_, block, else_block) => max_steps(block)?
+                .max(max_steps(else_block)?)
+                .checked_add(1)?,
+            Instruction::Repeat(u8::MAX, _) | Instruction::LabeledRepeat(_, u8::MAX, _) => {
+                return None;
+            }
+            Instruction::Repeat(iterations, block)
+            | Instruction::LabeledRepeat(_, iterations, block) => repeat_steps(*iterations, block)?,
+            // repeating by the register turns into a normal repeat, which takes a step
+            Instruction::RepeatRegister(block) => repeat_steps(u8::MAX, block)?.checked_add(1)?,
+            _ => 1,
+        };
+
+        total.checked_add(steps)
+    })
+}
+
 /// something that can be posted to a mini's log
 #[derive(Debug, PartialEq, Eq, Clone, Copy)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub enum Event {
     PostedRegister(u8),
     PostedFlare,
+    /// None if there's no living villager in that direction
+    PostedNeighborDistance(Direction, Option<u8>),
+    Checkpoint(u8),
+    PostedChecksum(u8),
+    /// the mini arrived at the villager with the label
+    Visited(u8),
+    /// the mini detonated and killed the villager with the label
+    Detonated(u8),
+    /// the mini detonated targeting the label, but nobody was killed
+    Fizzled(u8),
     Finished,
 }
 
 pub type EventLog = Vec<Event>;
 
+impl fmt::Display for Event {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Event::PostedRegister(value) => write!(f, "posted register {}", value),
+            Event::PostedFlare => write!(f, "posted flare"),
+            Event::PostedNeighborDistance(direction, distance) => {
+                let direction = match direction {
+                    Direction::Next => "next",
+                    Direction::Prev => "prev",
+                };
+                match distance {
+                    Some(distance) => write!(f, "posted dist {} {}", direction, distance),
+                    None => write!(f, "posted dist {} none", direction),
+                }
+            }
+            Event::Checkpoint(label) => write!(f, "checkpoint {}", label),
+            Event::PostedChecksum(checksum) => write!(f, "posted checksum {}", checksum),
+            Event::Visited(label) => write!(f, "visited {}", label),
+            Event::Detonated(label) => write!(f, "detonated {}", label),
+            Event::Fizzled(label) => write!(f, "fizzled at {}", label),
+            Event::Finished => write!(f, "finished"),
+        }
+    }
+}
+
+/// what destroyed a mini
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+pub enum DestroyReason {
+    /// a register went above u8::MAX
+    Overflow,
+    /// a register went below 0 (or there was nothing to pop)
+    Underflow,
+    DivideByZero,
+    /// the mini visited a murderer
+    Murderer,
+    /// the mini visited an afraid villager
+    Afraid,
+    SelfDetonate,
+}
+
 /// the status of a mini: running, or various ways for it to stop working
 #[derive(Debug, PartialEq, Eq, Clone, Copy)]
 pub enum MiniStatus {
     Running,
     Done,
-    Destroyed,
+    Destroyed(DestroyReason),
     Lost,
+    /// the mini tried to visit more villagers than its config allows
+    OutOfMoves,
+    /// the mini tried to run more instructions than its config allows
+    OutOfTime,
+}
+
+impl fmt::Display for MiniStatus {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            MiniStatus::Running => write!(f, "running"),
+            MiniStatus::Done => write!(f, "done"),
+            MiniStatus::Destroyed(reason) => write!(
+                f,
+                "destroyed {}",
+                match reason {
+                    DestroyReason::Overflow => "by a register overflowing",
+                    DestroyReason::Underflow => "by a register underflowing",
+                    DestroyReason::DivideByZero => "by dividing by zero",
+                    DestroyReason::Murderer => "by a murderer",
+                    DestroyReason::Afraid => "by an afraid villager",
+                    DestroyReason::SelfDetonate => "by its own detonation",
+                }
+            ),
+            MiniStatus::Lost => write!(f, "lost"),
+            MiniStatus::OutOfMoves => write!(f, "out of visits"),
+            MiniStatus::OutOfTime => write!(f, "out of time"),
+        }
+    }
+}
+
+/// which of a mini's sensors are enabled. sensors are what let a mini learn about
+/// the village; conditions relying on a disabled sensor never push their block
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+pub struct Sensors {
+    /// whether the mini can tell if the villager it's located at is alive or dead
+    pub villager_status: bool,
+    /// whether the mini can read the state of the village beyond the villager it's located
+    /// at, like whether other villagers are alive, how far away its nearest living
+    /// neighbors are, or where villagers are among the living
+    pub introspection: bool,
+}
+
+impl Sensors {
+    /// every sensor enabled
+    pub fn all() -> Self {
+        Self {
+            villager_status: true,
+            introspection: true,
+        }
+    }
+}
+
+impl Default for Sensors {
+    fn default() -> Self {
+        Self::all()
+    }
+}
+
+/// optional rules a mini can be run under. the default
+/// configuration matches the standard game
+#[derive(Debug, PartialEq, Eq, Clone, Copy, Default)]
+pub struct MiniConfig {
+    /// if true, detonate only kills the villager in the register if the mini is
+    /// currently located at that villager. the mini is destroyed either way
+    pub detonate_requires_presence: bool,
+    pub sensors: Sensors,
+    /// if true, the size of the instruction stack is recorded after every instruction
+    pub record_stack_depth: bool,
+    /// the most visits the mini can make. every visit counts (even repeat visits
+    /// to the same villager), including the visit to the starting location
+    pub max_visits: Option<usize>,
+    /// the most instructions the mini can run. this guards against programs
+    /// which would take practically forever to finish, like deeply nested repeats
+    pub instruction_budget: Option<u64>,
+}
+
+/// how well a program did on a village
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+pub struct Score {
+    pub murderers_eliminated: usize,
+    /// minis which were destroyed by a villager, lost, or ran out of moves or time.
+    /// minis which detonate themselves aren't counted
+    pub minis_lost: usize,
+}
+
+impl Score {
+    /// a single number to compare scores by. higher is better
+    pub fn value(&self) -> i64 {
+        self.murderers_eliminated as i64 - self.minis_lost as i64
+    }
+}
+
+/// runs each program from its best safe start on identical clones of the village (with
+/// their randomness seeded by the provided seed) and scores them. programs are returned
+/// best first, with ties keeping their original order
+#[allow(dead_code)]
+pub fn rank_programs(
+    programs: &[(String, Instructions)],
+    village: &Village,
+    seed: u64,
+) -> Vec<(String, Score)> {
+    let mut ranked: Vec<(String, Score)> = programs
+        .iter()
+        .map(|(name, program)| {
+            let mut village = village.clone();
+            village.reseed(seed);
+
+            // the lowest of the covering starts is always the lowest safe villager
+            let start = match village.covering_starts().first() {
+                Some(start) => *start,
+                None => {
+                    let score = Score {
+                        murderers_eliminated: 0,
+                        minis_lost: 0,
+                    };
+                    return (name.clone(), score);
+                }
+            };
+
+            let murderers = village.living_murderers();
+            let mut mini = Mini::new(start, program.clone(), &village)
+                .expect("covering starts are always existing villagers");
+            mini.run_until_completion(&mut village);
+
+            let lost = match mini.status {
+                MiniStatus::Destroyed(DestroyReason::SelfDetonate) => false,
+                MiniStatus::Destroyed(_)
+                | MiniStatus::Lost
+                | MiniStatus::OutOfMoves
+                | MiniStatus::OutOfTime => true,
+                MiniStatus::Running | MiniStatus::Done => false,
+            };
+
+            let score = Score {
+                murderers_eliminated: murderers - village.living_murderers(),
+                minis_lost: lost as usize,
+            };
+            (name.clone(), score)
+        })
+        .collect();
+
+    ranked.sort_by_key(|(_, score)| std::cmp::Reverse(score.value()));
+    ranked
 }
 
 /// a mini, along with all the information it needs to run:
@@ -62,68 +523,289 @@ pub enum MiniStatus {
 pub struct Mini {
     /// because this is a stack, the "next" instruction is at the end of the vector
     instruction_stack: Instructions,
+    /// the positions in the instruction stack of the repeat instructions belonging
+    /// to the loops currently being run. the innermost loop is at the end of the vector
+    loop_frames: Vec<usize>,
     register: u8,
+    register_b: u8,
+    /// values saved by push. the top of the stack is at the end of the vector
+    register_stack: Vec<u8>,
 
     status: MiniStatus,
     location: u8,
+    /// the locations the mini moved away from (not counting returns), with the most
+    /// recent at the end of the vector
+    history: Vec<u8>,
+    visits: usize,
+    /// the number of instructions run so far
+    steps: u64,
     log: EventLog,
+    /// the xor of every register value posted so far
+    checksum: u8,
+
+    config: MiniConfig,
+    /// only recorded if the config asks for it
+    stack_depth_history: Vec<usize>,
 }
 
 impl Mini {
     /// construct a new mini. requires a reference to a village so that the mini can
     /// visit its starting location
-    pub fn new(starting_location: u8, base_instructions: Instructions, village: &Village) -> Self {
+    pub fn new(
+        starting_location: u8,
+        base_instructions: Instructions,
+        village: &Village,
+    ) -> Result<Self, MiniError> {
+        Self::new_with_config(
+            starting_location,
+            base_instructions,
+            village,
+            MiniConfig::default(),
+        )
+    }
+
+    /// construct a new mini which runs under the provided rules
+    pub fn new_with_config(
+        starting_location: u8,
+        base_instructions: Instructions,
+        village: &Village,
+        config: MiniConfig,
+    ) -> Result<Self, MiniError> {
+        // a mini starting somewhere that doesn't exist would immediately be lost
+        if !village.villager_exists(starting_location) {
+            return Err(MiniError::NoSuchStartingVillager(starting_location));
+        }
+
         let mut mini = Self {
             instruction_stack: base_instructions,
+            loop_frames: Vec::new(),
             register: 0,
+            register_b: 0,
+            register_stack: Vec::new(),
             status: MiniStatus::Running,
             location: starting_location,
+            history: Vec::new(),
+            visits: 0,
+            steps: 0,
             log: Vec::new(),
+            checksum: 0,
+            config,
+            stack_depth_history: Vec::new(),
         };
 
+        // starting at a murderer or afraid villager still makes a mini, it's just destroyed
         mini.visit_villager(village, starting_location);
 
-        mini
+        Ok(mini)
+    }
+
+    pub fn status(&self) -> MiniStatus {
+        self.status
+    }
+
+    /// the value of register a
+    pub fn register(&self) -> u8 {
+        self.register
+    }
+
+    /// the label of the villager the mini is at
+    pub fn location(&self) -> u8 {
+        self.location
+    }
+
+    /// the number of instructions left on the instruction stack. nested blocks
+    /// only count once they've been pushed to the stack
+    pub fn stack_len(&self) -> usize {
+        self.instruction_stack.len()
     }
 
     pub fn log(&self) -> &EventLog {
         &self.log
     }
 
-    /// updates location (or becomes lost) and then carries out the
-    /// appropriate action according to the type of the visited villager
-    fn visit_villager(&mut self, village: &Village, location: u8) {
-        if !village.villager_exists(location) {
-            self.status = MiniStatus::Lost;
-            return;
+    /// the mini's log as a json array of events
+    #[cfg(feature = "serde")]
+    pub fn log_to_json(&self) -> String {
+        serde_json::to_string(&self.log).expect("events can always be serialized")
+    }
+
+    /// the mini's log as csv, with one row per event. events without a direction
+    /// or value leave those columns empty (as does a distance to nobody)
+    pub fn log_to_csv(&self) -> String {
+        let mut csv = String::from("index,event,direction,value\n");
+        for (index, event) in self.log.iter().enumerate() {
+            let (name, direction, value) = match *event {
+                Event::PostedRegister(value) => ("posted_register", "", Some(value)),
+                Event::PostedFlare => ("posted_flare", "", None),
+                Event::PostedNeighborDistance(direction, distance) => {
+                    let direction = match direction {
+                        Direction::Next => "next",
+                        Direction::Prev => "prev",
+                    };
+                    ("posted_neighbor_distance", direction, distance)
+                }
+                Event::Checkpoint(value) => ("checkpoint", "", Some(value)),
+                Event::PostedChecksum(value) => ("posted_checksum", "", Some(value)),
+                Event::Visited(label) => ("visited", "", Some(label)),
+                Event::Detonated(label) => ("detonated", "", Some(label)),
+                Event::Fizzled(label) => ("fizzled", "", Some(label)),
+                Event::Finished => ("finished", "", None),
+            };
+            let value = value.map(|value| value.to_string()).unwrap_or_default();
+            csv.push_str(&format!("{},{},{},{}\n", index, name, direction, value));
         }
 
-        self.location = location;
+        csv
+    }
 
-        // if the villager is dead, we shouldn't do anything
-        if village.dead_villager(location).is_some() {
-            return;
+    /// the size of the instruction stack after each instruction was run. empty
+    /// unless the mini's config has record_stack_depth enabled
+    #[allow(dead_code)]
+    pub fn stack_depth_history(&self) -> &[usize] {
+        &self.stack_depth_history
+    }
+
+    /// visits a villager, remembering where the mini came from so it can return
+    fn move_to(&mut self, village: &Village, location: u8) {
+        let previous = self.location;
+        if self.visit_villager(village, location) {
+            self.history.push(previous);
         }
+    }
 
-        // do whatever the visited villager's type entails
-        match village
-            .villager_type(location)
-            .expect("just confirmed villager exists")
+    /// updates location (or becomes lost) and then carries out the
+    /// appropriate action according to the type of the visited villager.
+    /// returns whether the mini made it to the location
+    fn visit_villager(&mut self, village: &Village, location: u8) -> bool {
+        if let Some(max_visits) = self.config.max_visits
+            && self.visits >= max_visits
         {
-            VillagerType::Murderer => {
-                self.status = MiniStatus::Destroyed;
+            self.status = MiniStatus::OutOfMoves;
+            return false;
+        }
+        self.visits += 1;
+
+        let safety = match village.visit_safety(location) {
+            Some(safety) => safety,
+            None => {
+                self.status = MiniStatus::Lost;
+                return false;
+            }
+        };
+
+        self.location = location;
+        self.log.push(Event::Visited(location));
+
+        // dead villagers don't do anything, and living villagers only do something
+        // if they're the type to destroy minis. murderers also clear the mini's log
+        // (including the visit to them)
+        if safety == VisitSafety::Destroys {
+            if village.villager_type(location) == Ok(VillagerType::Murderer) {
+                self.status = MiniStatus::Destroyed(DestroyReason::Murderer);
                 self.log = Vec::new();
+            } else {
+                self.status = MiniStatus::Destroyed(DestroyReason::Afraid);
+            }
+        }
+
+        true
+    }
+
+    /// whether a flare is present in the mini's log
+    fn has_flared(&self) -> bool {
+        self.log.contains(&Event::PostedFlare)
+    }
+
+    /// checks whether a condition holds. None if the mini
+    /// doesn't have the sensor needed to check the condition
+    fn check_condition(&self, village: &Village, condition: Condition) -> Option<bool> {
+        let sensors = self.config.sensors;
+
+        match condition {
+            Condition::VillagerIsAlive if sensors.villager_status => {
+                Some(village.living_villager(self.location).is_some())
+            }
+            Condition::VillagerIsDead if sensors.villager_status => {
+                Some(village.dead_villager(self.location).is_some())
+            }
+            Condition::VillagerIsAlive | Condition::VillagerIsDead => None,
+
+            Condition::RegisterTargetDead if sensors.introspection => {
+                Some(village.dead_villager(self.register).is_some())
+            }
+            Condition::RegisterTargetDead => None,
+
+            Condition::LabelAlive(label) | Condition::LabelDead(label)
+                if sensors.introspection && village.villager_exists(label) =>
+            {
+                let alive = village.living_villager(label).is_some();
+                Some(alive == matches!(condition, Condition::LabelAlive(_)))
             }
-            VillagerType::Afraid => self.status = MiniStatus::Destroyed,
-            _ => (),
+            Condition::LabelAlive(_) | Condition::LabelDead(_) => None,
+
+            Condition::RegisterEq(value) => Some(self.register == value),
+            Condition::RegisterGreater(value) => Some(self.register > value),
+            Condition::RegisterLess(value) => Some(self.register < value),
+            Condition::RegisterEven => Some(self.register.is_multiple_of(2)),
+            Condition::RegisterOdd => Some(!self.register.is_multiple_of(2)),
+            Condition::HasPostedFlare => Some(self.has_flared()),
+        }
+    }
+
+    /// visits the nearest living villager in the provided direction, getting lost if there isn't one
+    fn visit_neighbor(&mut self, village: &Village, direction: Direction) {
+        match village.nearest_living(self.location, direction) {
+            Some(label) => self.move_to(village, label),
+            None => self.status = MiniStatus::Lost,
+        }
+    }
+
+    /// kill the target villager (if the config allows it) and destroy the mini
+    fn detonate(&mut self, village: &mut Village, target: u8) {
+        let killed = (!self.config.detonate_requires_presence || target == self.location)
+            && village.kill_villager(target).is_ok();
+
+        self.log.push(if killed {
+            Event::Detonated(target)
+        } else {
+            Event::Fizzled(target)
+        });
+
+        self.status = MiniStatus::Destroyed(DestroyReason::SelfDetonate);
+    }
+
+    /// sets the register to the result of the operation, or destroys the
+    /// mini for the provided reason if the operation doesn't have a result
+    fn apply_checked(&mut self, operation: impl FnOnce(u8) -> Option<u8>, reason: DestroyReason) {
+        match operation(self.register) {
+            Some(value) => self.register = value,
+            None => self.status = MiniStatus::Destroyed(reason),
+        }
+    }
+
+    fn register_mut(&mut self, register: Register) -> &mut u8 {
+        match register {
+            Register::A => &mut self.register,
+            Register::B => &mut self.register_b,
+        }
+    }
+
+    /// pop the top instruction off the instruction stack, leaving the
+    /// loop it belonged to if it was the repeat instruction of that loop
+    fn pop_instruction(&mut self) -> Option<Instruction> {
+        let instruction = self.instruction_stack.pop();
+        if self.loop_frames.last() == Some(&self.instruction_stack.len()) {
+            self.loop_frames.pop();
         }
+
+        instruction
     }
 
     /// pop the top instruction off the instruction stack and run it
     fn run_instruction(&mut self, village: &mut Village) {
         // get the next instruction. if there are no more instructions, set
         // our status to done
-        let instruction = match self.instruction_stack.pop() {
+        let instruction = match self.pop_instruction() {
             Some(instruction) => instruction,
             None => {
                 self.status = MiniStatus::Done;
@@ -131,55 +813,166 @@ impl Mini {
             }
         };
 
+        if let Some(budget) = self.config.instruction_budget
+            && self.steps >= budget
+        {
+            self.status = MiniStatus::OutOfTime;
+            return;
+        }
+        self.steps += 1;
+
         // match the instruction against every possible value and
         // do whatever is required by the instruction
         match instruction {
             Instruction::Action(Action::PostRegister) => {
+                self.checksum ^= self.register;
                 self.log.push(Event::PostedRegister(self.register))
             }
+            Instruction::Action(Action::PostChecksum) => {
+                self.log.push(Event::PostedChecksum(self.checksum))
+            }
             Instruction::Action(Action::PostFlare) => self.log.push(Event::PostedFlare),
-            Instruction::Action(Action::Detonate) => {
-                let _ = village.kill_villager(self.register);
-                self.status = MiniStatus::Destroyed;
+            Instruction::Action(Action::PostFlareOnce) => {
+                if !self.has_flared() {
+                    self.log.push(Event::PostedFlare)
+                }
+            }
+            Instruction::Action(Action::PostNeighborDistance(direction)) => {
+                if self.config.sensors.introspection {
+                    let distance = village
+                        .nearest_living(self.location, direction)
+                        .map(|neighbor| neighbor.abs_diff(self.location));
+                    self.log
+                        .push(Event::PostedNeighborDistance(direction, distance))
+                }
+            }
+            Instruction::Action(Action::Detonate) => self.detonate(village, self.register),
+            Instruction::Action(Action::DetonateAt(target)) => self.detonate(village, target),
+            Instruction::Action(Action::DetonateIfValid) => {
+                if village.living_villager(self.register).is_some() {
+                    self.detonate(village, self.register)
+                }
+            }
+            Instruction::Action(Action::Visit) => self.move_to(village, self.register),
+            Instruction::Action(Action::VisitNth) => {
+                if self.config.sensors.introspection {
+                    match village.nth_living(self.register as usize) {
+                        Some(label) => self.move_to(village, label),
+                        None => self.status = MiniStatus::Lost,
+                    }
+                }
+            }
+            Instruction::Action(Action::Return) => {
+                if let Some(location) = self.history.pop() {
+                    self.visit_villager(village, location);
+                }
+            }
+            Instruction::Action(Action::ReadLocation) => self.register = self.location,
+            Instruction::Action(Action::CountLiving) => {
+                if self.config.sensors.introspection {
+                    self.register = village.living_count().try_into().unwrap_or(u8::MAX)
+                }
+            }
+            Instruction::Action(Action::Randomize(max)) => {
+                self.register = village.random_up_to(max)
+            }
+            Instruction::Action(Action::VisitUp) => self.visit_neighbor(village, Direction::Next),
+            Instruction::Action(Action::VisitDown) => self.visit_neighbor(village, Direction::Prev),
+            Instruction::Action(Action::Checkpoint(label)) => {
+                self.log.push(Event::Checkpoint(label))
             }
-            Instruction::Action(Action::Visit) => self.visit_villager(village, self.register),
 
-            Instruction::Operation(Operation::Increment) => {
+            Instruction::Operation(Operation::Increment(register)) => {
                 // destroy the mini if we'd encounter overflow
-                if self.register == u8::MAX {
-                    self.status = MiniStatus::Destroyed
+                let register = self.register_mut(register);
+                if *register == u8::MAX {
+                    self.status = MiniStatus::Destroyed(DestroyReason::Overflow)
                 } else {
-                    self.register += 1
+                    *register += 1
                 }
             }
-            Instruction::Operation(Operation::Decrement) => {
+            Instruction::Operation(Operation::Decrement(register)) => {
                 // destroy the mini if we'd encounter underflow
-                if self.register == 0 {
-                    self.status = MiniStatus::Destroyed
+                let register = self.register_mut(register);
+                if *register == 0 {
+                    self.status = MiniStatus::Destroyed(DestroyReason::Underflow)
                 } else {
-                    self.register -= 1;
+                    *register -= 1;
                 }
             }
-            Instruction::Operation(Operation::SetValue(value)) => self.register = value,
-
-            Instruction::Condition(Condition::VillagerIsAlive, instructions) => {
-                // if the villager we're at is alive, push the conditional
-                // instructions to the stack
-                if village.living_villager(self.location).is_some() {
-                    self.instruction_stack.extend(instructions);
+            Instruction::Operation(Operation::SetValue(register, value)) => {
+                *self.register_mut(register) = value
+            }
+            Instruction::Operation(Operation::RotateLeft(amount)) => {
+                self.register = self.register.rotate_left(amount as u32)
+            }
+            Instruction::Operation(Operation::RotateRight(amount)) => {
+                self.register = self.register.rotate_right(amount as u32)
+            }
+            Instruction::Operation(Operation::LoadLoopCounter) => {
+                // outside of a loop, there's nothing to load
+                if let Some(&frame) = self.loop_frames.last()
+                    && let Some(
+                        Instruction::Repeat(remaining, _)
+                        | Instruction::LabeledRepeat(_, remaining, _),
+                    ) = self.instruction_stack.get(frame)
+                {
+                    self.register = *remaining;
                 }
             }
-            Instruction::Condition(Condition::VillagerIsDead, instructions) => {
-                // if the villager we're at is dead, push the conditional
-                // instructions to the stack
-                if village.dead_villager(self.location).is_some() {
-                    self.instruction_stack.extend(instructions);
+            Instruction::Operation(Operation::AbsDiff(value)) => {
+                self.register = self.register.abs_diff(value)
+            }
+            Instruction::Operation(Operation::AddImmediate(value)) => self.apply_checked(
+                |register| register.checked_add(value),
+                DestroyReason::Overflow,
+            ),
+            Instruction::Operation(Operation::SubImmediate(value)) => self.apply_checked(
+                |register| register.checked_sub(value),
+                DestroyReason::Underflow,
+            ),
+            Instruction::Operation(Operation::MultiplyBy(value)) => self.apply_checked(
+                |register| register.checked_mul(value),
+                DestroyReason::Overflow,
+            ),
+            Instruction::Operation(Operation::DivideBy(value)) => self.apply_checked(
+                |register| register.checked_div(value),
+                DestroyReason::DivideByZero,
+            ),
+            Instruction::Operation(Operation::ModuloBy(value)) => self.apply_checked(
+                |register| register.checked_rem(value),
+                DestroyReason::DivideByZero,
+            ),
+            Instruction::Operation(Operation::SwapRegisterLocation) => {
+                // this intentionally doesn't visit the new location, so the villager
+                // there won't have any effect on the mini until it's visited
+                std::mem::swap(&mut self.register, &mut self.location)
+            }
+            Instruction::Operation(Operation::SwapRegisters) => {
+                std::mem::swap(&mut self.register, &mut self.register_b)
+            }
+            Instruction::Operation(Operation::PushRegister) => {
+                self.register_stack.push(self.register)
+            }
+            Instruction::Operation(Operation::PopRegister) => match self.register_stack.pop() {
+                Some(value) => self.register = value,
+                // like underflow, there's nothing sensible to do here
+                None => self.status = MiniStatus::Destroyed(DestroyReason::Underflow),
+            },
+
+            Instruction::Condition(condition, instructions) => {
+                // if the condition holds, push the conditional instructions to the stack
+                if self.check_condition(village, condition) == Some(true) {
+                    self.instruction_stack.extend(instructions.iter().cloned());
                 }
             }
-            Instruction::Condition(Condition::RegisterEq(value), instructions) => {
-                // if register is equal to the test value, push the conditional instructions to the stack
-                if self.register == value {
-                    self.instruction_stack.extend(instructions);
+            Instruction::ConditionElse(condition, instructions, else_instructions) => {
+                match self.check_condition(village, condition) {
+                    Some(true) => self.instruction_stack.extend(instructions.iter().cloned()),
+                    Some(false) => self
+                        .instruction_stack
+                        .extend(else_instructions.iter().cloned()),
+                    None => (),
                 }
             }
 
@@ -188,34 +981,101 @@ impl Mini {
                 // put the repeated instructions to the stack along with a copy of this
                 // repeat command with the remaining iteration count decreased by one
                 if iterations != 0 {
+                    self.loop_frames.push(self.instruction_stack.len());
                     self.instruction_stack
                         .push(Instruction::Repeat(iterations - 1, instructions.clone()));
-                    self.instruction_stack.extend(instructions);
+                    self.instruction_stack.extend(instructions.iter().cloned());
+                }
+            }
+            Instruction::LabeledRepeat(label, iterations, instructions) => {
+                // exactly like a normal repeat
+                if iterations != 0 {
+                    self.loop_frames.push(self.instruction_stack.len());
+                    self.instruction_stack.push(Instruction::LabeledRepeat(
+                        label,
+                        iterations - 1,
+                        instructions.clone(),
+                    ));
+                    self.instruction_stack.extend(instructions.iter().cloned());
                 }
             }
+            Instruction::RepeatRegister(instructions) => {
+                // the register is only read here, so changing it inside the loop won't
+                // change how many times the loop runs
+                self.instruction_stack
+                    .push(Instruction::Repeat(self.register, instructions));
+            }
 
-            Instruction::Break => loop {
-                // keep removing instructions from the stack until we've removed everything
-                // or encountered and removed a repeat instruction (which will end up being
-                // the most nested repeat)
-                match self.instruction_stack.pop() {
-                    None => {
-                        self.status = MiniStatus::Done;
-                        break;
-                    }
-                    Some(Instruction::Repeat(_, _)) => break,
-                    Some(_) => (),
+            Instruction::Break => self.break_loop(),
+            Instruction::BreakTo(label) => self.break_to(&label),
+            Instruction::BreakIf(condition) => {
+                if self.check_condition(village, condition) == Some(true) {
+                    self.break_loop()
                 }
+            }
+            Instruction::Continue => match self.loop_frames.last() {
+                // throw away everything above the innermost loop's repeat instruction,
+                // which will then start the next iteration
+                Some(frame) => self.instruction_stack.truncate(frame + 1),
+                // like break, continuing outside of a loop ends the program
+                None => self.break_loop(),
             },
+            Instruction::Noop => (),
+        }
+    }
+
+    /// leave the innermost loop, or finish the program if we aren't in one
+    fn break_loop(&mut self) {
+        // keep removing instructions from the stack until we've removed everything
+        // or encountered and removed a repeat instruction (which will end up being
+        // the most nested repeat)
+        loop {
+            match self.pop_instruction() {
+                None => {
+                    self.status = MiniStatus::Done;
+                    break;
+                }
+                Some(Instruction::Repeat(_, _) | Instruction::LabeledRepeat(_, _, _)) => break,
+                Some(_) => (),
+            }
+        }
+    }
+
+    /// leave every loop up to and including the innermost labeled repeat with the
+    /// provided label, or finish the program if there isn't one
+    fn break_to(&mut self, label: &str) {
+        loop {
+            match self.pop_instruction() {
+                None => {
+                    self.status = MiniStatus::Done;
+                    break;
+                }
+                Some(Instruction::LabeledRepeat(repeat_label, _, _)) if repeat_label == label => {
+                    break;
+                }
+                Some(_) => (),
+            }
         }
     }
 
     /// keep running instructions on the instruction stack until
     /// the state changes from running. the first instruction
-    /// should be visit.
+    /// should be visit. if the program uses `rand`, this is only
+    /// deterministic if the village has been seeded
     pub fn run_until_completion(&mut self, village: &mut Village) {
-        while self.status == MiniStatus::Running {
-            self.run_instruction(village);
+        while self.step(village) == MiniStatus::Running {}
+    }
+
+    /// runs the next instruction on the instruction stack and returns the mini's
+    /// status afterwards. does nothing if the mini has already stopped running
+    pub fn step(&mut self, village: &mut Village) -> MiniStatus {
+        if self.status != MiniStatus::Running {
+            return self.status;
+        }
+
+        self.run_instruction(village);
+        if self.config.record_stack_depth {
+            self.stack_depth_history.push(self.instruction_stack.len());
         }
 
         // if we finished gracefully (i.e. weren't destroyed or anything,
@@ -223,21 +1083,33 @@ impl Mini {
         if self.status == MiniStatus::Done {
             self.log.push(Event::Finished);
         }
+
+        self.status
     }
 }
 
+/// represents anything that can go wrong constructing a mini
+#[derive(Error, Debug, PartialEq, Eq)]
+pub enum MiniError {
+    /// the u8 represents the starting location which couldn't be found
+    #[error("there is no villager `{0}` to start at")]
+    NoSuchStartingVillager(u8),
+}
+
 #[cfg(test)]
 mod test {
     // recall in all of these tests that the instruction
     // stack is read back to front
-    use std::vec;
+    use std::{rc::Rc, vec};
 
     use crate::{
-        mini::{Event, MiniStatus},
-        village::{LivingVillager, Village, Villager, VillagerType},
+        mini::{
+            DestroyReason, Event, MiniConfig, MiniError, MiniStatus, Score, Sensors, max_steps,
+        },
+        village::{Direction, LivingVillager, Village, Villager, VillagerType},
     };
 
-    use super::{Action, Condition, Instruction, Mini, Operation};
+    use super::{Action, Condition, Instruction, Mini, Operation, Register};
 
     #[test]
     fn register_operations() {
@@ -246,186 +1118,492 @@ mod test {
         let mut mini = Mini::new(
             1,
             vec![
-                Instruction::Operation(Operation::Decrement),
-                Instruction::Operation(Operation::SetValue(10)),
-                Instruction::Operation(Operation::Decrement),
-                Instruction::Operation(Operation::Increment),
-                Instruction::Operation(Operation::Increment),
+                Instruction::Operation(Operation::Decrement(Register::A)),
+                Instruction::Operation(Operation::SetValue(Register::A, 10)),
+                Instruction::Operation(Operation::Decrement(Register::A)),
+                Instruction::Operation(Operation::Increment(Register::A)),
+                Instruction::Operation(Operation::Increment(Register::A)),
             ],
             &village,
-        );
+        )
+        .unwrap();
 
-        assert_eq!(mini.register, 0);
+        assert_eq!(mini.register(), 0);
         mini.run_instruction(&mut village);
-        assert_eq!(mini.register, 1);
+        assert_eq!(mini.register(), 1);
         mini.run_instruction(&mut village);
-        assert_eq!(mini.register, 2);
+        assert_eq!(mini.register(), 2);
         mini.run_instruction(&mut village);
-        assert_eq!(mini.register, 1);
+        assert_eq!(mini.register(), 1);
         mini.run_instruction(&mut village);
-        assert_eq!(mini.register, 10);
+        assert_eq!(mini.register(), 10);
         mini.run_instruction(&mut village);
-        assert_eq!(mini.register, 9);
+        assert_eq!(mini.register(), 9);
 
-        assert_eq!(mini.status, MiniStatus::Running);
+        assert_eq!(mini.status(), MiniStatus::Running);
     }
 
     #[test]
     fn register_safety() {
-        let mut village = Village::new_deterministic(Vec::new());
+        let mut village = Village::new_deterministic(vec![Villager::new(VillagerType::Normal, 0)]);
 
         let mut mini = Mini::new(
             0,
-            vec![Instruction::Operation(Operation::Decrement)],
+            vec![Instruction::Operation(Operation::Decrement(Register::A))],
             &village,
-        );
+        )
+        .unwrap();
 
-        assert_eq!(mini.register, 0);
+        assert_eq!(mini.register(), 0);
         mini.run_instruction(&mut village);
-        assert_eq!(mini.status, MiniStatus::Destroyed);
-        assert_eq!(mini.register, 0);
+        assert_eq!(
+            mini.status(),
+            MiniStatus::Destroyed(DestroyReason::Underflow)
+        );
+        assert_eq!(mini.register(), 0);
 
         let mut mini = Mini::new(
             0,
             vec![
-                Instruction::Operation(Operation::Increment),
-                Instruction::Operation(Operation::SetValue(u8::MAX)),
+                Instruction::Operation(Operation::Increment(Register::A)),
+                Instruction::Operation(Operation::SetValue(Register::A, u8::MAX)),
             ],
             &village,
-        );
+        )
+        .unwrap();
 
         mini.run_instruction(&mut village);
-        assert_eq!(mini.register, u8::MAX);
+        assert_eq!(mini.register(), u8::MAX);
         mini.run_instruction(&mut village);
-        assert_eq!(mini.status, MiniStatus::Destroyed);
-        assert_eq!(mini.register, u8::MAX);
+        assert_eq!(
+            mini.status(),
+            MiniStatus::Destroyed(DestroyReason::Overflow)
+        );
+        assert_eq!(mini.register(), u8::MAX);
     }
 
     #[test]
-    fn visiting() {
-        let villagers: Vec<LivingVillager> = (1..=4)
-            .map(|i| Villager::new(VillagerType::Normal, i))
-            .collect();
-        let mut village = Village::new_deterministic(villagers);
+    fn rotations() {
+        let mut village = Village::new_deterministic(vec![Villager::new(VillagerType::Normal, 1)]);
 
         let mut mini = Mini::new(
-            4,
+            1,
             vec![
-                Instruction::Action(Action::Visit),
-                Instruction::Operation(Operation::Increment),
-                Instruction::Action(Action::Visit),
-                Instruction::Operation(Operation::SetValue(2)),
+                Instruction::Operation(Operation::RotateRight(11)),
+                Instruction::Operation(Operation::RotateLeft(3)),
+                Instruction::Operation(Operation::SetValue(Register::A, 0b1011_0001)),
             ],
             &village,
-        );
+        )
+        .unwrap();
 
-        assert_eq!(mini.location, 4);
-        mini.run_instruction(&mut village);
         mini.run_instruction(&mut village);
-        assert_eq!(mini.location, 2);
         mini.run_instruction(&mut village);
+        assert_eq!(mini.register(), 0b1011_0001u8.rotate_left(3));
         mini.run_instruction(&mut village);
-        assert_eq!(mini.location, 3);
+        assert_eq!(
+            mini.register(),
+            0b1011_0001u8.rotate_left(3).rotate_right(11)
+        );
 
-        assert_eq!(mini.status, MiniStatus::Running);
-        (1..=4).for_each(|i| assert!(village.living_villager(i).is_some()));
+        assert_eq!(mini.status(), MiniStatus::Running);
     }
 
     #[test]
-    fn actions() {
-        let villagers: Vec<LivingVillager> = (1..=4)
-            .map(|i| Villager::new(VillagerType::Normal, i))
-            .collect();
-        let mut village = Village::new_deterministic(villagers);
+    fn abs_diff() {
+        let mut village = Village::new_deterministic(vec![Villager::new(VillagerType::Normal, 1)]);
 
         let mut mini = Mini::new(
             1,
             vec![
-                Instruction::Action(Action::Detonate),
-                Instruction::Action(Action::Visit),
-                Instruction::Action(Action::PostRegister),
-                Instruction::Action(Action::PostFlare),
-                Instruction::Operation(Operation::SetValue(2)),
-                Instruction::Action(Action::PostRegister),
+                Instruction::Operation(Operation::AbsDiff(10)),
+                Instruction::Operation(Operation::SetValue(Register::A, 13)),
+                Instruction::Operation(Operation::AbsDiff(10)),
+                Instruction::Operation(Operation::SetValue(Register::A, 7)),
             ],
             &village,
-        );
+        )
+        .unwrap();
 
-        mini.run_instruction(&mut village);
-        assert_eq!(mini.log, vec![Event::PostedRegister(0)]);
         mini.run_instruction(&mut village);
         mini.run_instruction(&mut village);
+        assert_eq!(mini.register(), 3);
         mini.run_instruction(&mut village);
-        assert_eq!(
-            mini.log,
-            vec![
-                Event::PostedRegister(0),
-                Event::PostedFlare,
-                Event::PostedRegister(2)
-            ]
-        );
-
-        mini.run_instruction(&mut village);
-        assert_eq!(mini.status, MiniStatus::Running);
         mini.run_instruction(&mut village);
-        assert_eq!(mini.status, MiniStatus::Destroyed);
-        assert!(village.dead_villager(2).is_some());
+        assert_eq!(mini.register(), 3);
+        assert_eq!(mini.status(), MiniStatus::Running);
     }
 
     #[test]
-    fn dies_to_murderer() {
-        let mut villagers: Vec<LivingVillager> = (1..=4)
+    fn swap_register_location() {
+        let villagers: Vec<LivingVillager> = (1..=4)
             .map(|i| Villager::new(VillagerType::Normal, i))
             .collect();
-        villagers.push(Villager::new(VillagerType::Murderer, 5));
         let mut village = Village::new_deterministic(villagers);
 
         let mut mini = Mini::new(
-            1,
+            4,
             vec![
-                Instruction::Action(Action::Visit),
-                Instruction::Operation(Operation::SetValue(5)),
+                Instruction::Operation(Operation::SwapRegisterLocation),
+                Instruction::Operation(Operation::SetValue(Register::A, 2)),
             ],
             &village,
-        );
+        )
+        .unwrap();
 
         mini.run_instruction(&mut village);
         mini.run_instruction(&mut village);
-        assert_eq!(mini.status, MiniStatus::Destroyed);
+        assert_eq!(mini.register(), 4);
+        assert_eq!(mini.location(), 2);
     }
 
     #[test]
-    fn completes_correctly() {
+    fn visiting() {
         let villagers: Vec<LivingVillager> = (1..=4)
             .map(|i| Villager::new(VillagerType::Normal, i))
             .collect();
         let mut village = Village::new_deterministic(villagers);
 
         let mut mini = Mini::new(
-            1,
+            4,
             vec![
-                Instruction::Operation(Operation::Increment),
-                Instruction::Break,
+                Instruction::Action(Action::Visit),
+                Instruction::Operation(Operation::Increment(Register::A)),
+                Instruction::Action(Action::Visit),
+                Instruction::Operation(Operation::SetValue(Register::A, 2)),
             ],
             &village,
-        );
+        )
+        .unwrap();
 
+        assert_eq!(mini.location(), 4);
         mini.run_instruction(&mut village);
-        assert_eq!(mini.status, MiniStatus::Done);
-
-        let mut mini = Mini::new(
-            1,
+        mini.run_instruction(&mut village);
+        assert_eq!(mini.location(), 2);
+        mini.run_instruction(&mut village);
+        mini.run_instruction(&mut village);
+        assert_eq!(mini.location(), 3);
+        assert_eq!(
+            mini.log,
+            vec![Event::Visited(4), Event::Visited(2), Event::Visited(3)]
+        );
+
+        assert_eq!(mini.status(), MiniStatus::Running);
+        (1..=4).for_each(|i| assert!(village.living_villager(i).is_some()));
+    }
+
+    #[test]
+    fn max_visits() {
+        let villagers: Vec<LivingVillager> = (1..=3)
+            .map(|i| Villager::new(VillagerType::Normal, i))
+            .collect();
+        let mut village = Village::new_deterministic(villagers);
+        let config = MiniConfig {
+            max_visits: Some(2),
+            ..Default::default()
+        };
+
+        // visiting the starting location is the first visit
+        let mut mini = Mini::new_with_config(
+            1,
+            vec![
+                Instruction::Action(Action::Visit),
+                Instruction::Operation(Operation::SetValue(Register::A, 3)),
+                Instruction::Action(Action::Visit),
+                Instruction::Operation(Operation::SetValue(Register::A, 2)),
+            ],
+            &village,
+            config,
+        )
+        .unwrap();
+
+        mini.run_instruction(&mut village);
+        mini.run_instruction(&mut village);
+        assert_eq!(mini.location(), 2);
+        assert_eq!(mini.status(), MiniStatus::Running);
+
+        mini.run_instruction(&mut village);
+        mini.run_instruction(&mut village);
+        assert_eq!(mini.location(), 2);
+        assert_eq!(mini.status(), MiniStatus::OutOfMoves);
+    }
+
+    #[test]
+    fn actions() {
+        let villagers: Vec<LivingVillager> = (1..=4)
+            .map(|i| Villager::new(VillagerType::Normal, i))
+            .collect();
+        let mut village = Village::new_deterministic(villagers);
+
+        let mut mini = Mini::new(
+            1,
+            vec![
+                Instruction::Action(Action::Detonate),
+                Instruction::Action(Action::Visit),
+                Instruction::Action(Action::PostRegister),
+                Instruction::Action(Action::PostFlare),
+                Instruction::Operation(Operation::SetValue(Register::A, 2)),
+                Instruction::Action(Action::PostRegister),
+            ],
+            &village,
+        )
+        .unwrap();
+
+        mini.run_instruction(&mut village);
+        assert_eq!(mini.log, vec![Event::Visited(1), Event::PostedRegister(0)]);
+        mini.run_instruction(&mut village);
+        mini.run_instruction(&mut village);
+        mini.run_instruction(&mut village);
+        assert_eq!(
+            mini.log,
+            vec![
+                Event::Visited(1),
+                Event::PostedRegister(0),
+                Event::PostedFlare,
+                Event::PostedRegister(2)
+            ]
+        );
+
+        mini.run_instruction(&mut village);
+        assert_eq!(mini.status(), MiniStatus::Running);
+        mini.run_instruction(&mut village);
+        assert_eq!(
+            mini.status(),
+            MiniStatus::Destroyed(DestroyReason::SelfDetonate)
+        );
+        assert!(village.dead_villager(2).is_some());
+    }
+
+    #[test]
+    fn detonate_if_valid() {
+        let mut village = Village::new_deterministic(vec![
+            Villager::new(VillagerType::Normal, 1),
+            Villager::new(VillagerType::Normal, 2),
+        ]);
+
+        let mut mini = Mini::new(
+            1,
+            vec![
+                Instruction::Action(Action::DetonateIfValid),
+                Instruction::Operation(Operation::SetValue(Register::A, 2)),
+                Instruction::Action(Action::DetonateIfValid),
+                Instruction::Operation(Operation::SetValue(Register::A, 7)),
+            ],
+            &village,
+        )
+        .unwrap();
+
+        // there's no villager 7, so nothing should happen
+        mini.run_instruction(&mut village);
+        mini.run_instruction(&mut village);
+        assert_eq!(mini.status(), MiniStatus::Running);
+
+        mini.run_instruction(&mut village);
+        mini.run_instruction(&mut village);
+        assert_eq!(
+            mini.status(),
+            MiniStatus::Destroyed(DestroyReason::SelfDetonate)
+        );
+        assert!(village.dead_villager(2).is_some());
+    }
+
+    #[test]
+    fn detonate_requires_presence() {
+        let villagers: Vec<LivingVillager> = (1..=2)
+            .map(|i| Villager::new(VillagerType::Normal, i))
+            .collect();
+        let mut village = Village::new_deterministic(villagers);
+        let config = MiniConfig {
+            detonate_requires_presence: true,
+            ..Default::default()
+        };
+
+        // the mini is at villager 1 but tries to detonate villager 2
+        let mut mini = Mini::new_with_config(
+            1,
+            vec![
+                Instruction::Action(Action::Detonate),
+                Instruction::Operation(Operation::SetValue(Register::A, 2)),
+            ],
+            &village,
+            config,
+        )
+        .unwrap();
+
+        mini.run_until_completion(&mut village);
+        assert_eq!(
+            mini.status(),
+            MiniStatus::Destroyed(DestroyReason::SelfDetonate)
+        );
+        assert!(village.living_villager(2).is_some());
+
+        // the mini is at villager 2 and detonates villager 2
+        let mut mini = Mini::new_with_config(
+            2,
+            vec![
+                Instruction::Action(Action::Detonate),
+                Instruction::Operation(Operation::SetValue(Register::A, 2)),
+            ],
+            &village,
+            config,
+        )
+        .unwrap();
+
+        mini.run_until_completion(&mut village);
+        assert_eq!(
+            mini.status(),
+            MiniStatus::Destroyed(DestroyReason::SelfDetonate)
+        );
+        assert!(village.dead_villager(2).is_some());
+    }
+
+    #[test]
+    fn checkpoints() {
+        let mut village = Village::new_deterministic(vec![Villager::new(VillagerType::Normal, 1)]);
+
+        let mut mini = Mini::new(
+            1,
+            vec![
+                Instruction::Action(Action::Checkpoint(2)),
+                Instruction::Action(Action::PostFlare),
+                Instruction::Action(Action::Checkpoint(1)),
+                Instruction::Action(Action::PostRegister),
+            ],
+            &village,
+        )
+        .unwrap();
+
+        mini.run_until_completion(&mut village);
+        assert_eq!(
+            mini.log,
+            vec![
+                Event::Visited(1),
+                Event::PostedRegister(0),
+                Event::Checkpoint(1),
+                Event::PostedFlare,
+                Event::Checkpoint(2),
+                Event::Finished
+            ]
+        );
+    }
+
+    #[test]
+    fn neighbor_distance() {
+        let villagers: Vec<LivingVillager> = (1..=6)
+            .map(|i| Villager::new(VillagerType::Normal, i))
+            .collect();
+        let mut village = Village::new_deterministic(villagers);
+        village.kill_villager(2).unwrap();
+        village.kill_villager(4).unwrap();
+        village.kill_villager(5).unwrap();
+
+        let mut mini = Mini::new(
+            3,
+            vec![
+                Instruction::Action(Action::PostNeighborDistance(Direction::Next)),
+                Instruction::Action(Action::Visit),
+                Instruction::Operation(Operation::SetValue(Register::A, 6)),
+                Instruction::Action(Action::PostNeighborDistance(Direction::Prev)),
+                Instruction::Action(Action::PostNeighborDistance(Direction::Next)),
+            ],
+            &village,
+        )
+        .unwrap();
+
+        mini.run_until_completion(&mut village);
+        assert_eq!(
+            mini.log,
+            vec![
+                Event::Visited(3),
+                Event::PostedNeighborDistance(Direction::Next, Some(3)),
+                Event::PostedNeighborDistance(Direction::Prev, Some(2)),
+                Event::Visited(6),
+                Event::PostedNeighborDistance(Direction::Next, None),
+                Event::Finished
+            ]
+        );
+
+        // without introspection, nothing is posted
+        let config = MiniConfig {
+            sensors: Sensors {
+                introspection: false,
+                ..Default::default()
+            },
+            ..Default::default()
+        };
+        let mut mini = Mini::new_with_config(
+            3,
+            vec![Instruction::Action(Action::PostNeighborDistance(
+                Direction::Next,
+            ))],
+            &village,
+            config,
+        )
+        .unwrap();
+        mini.run_until_completion(&mut village);
+        assert_eq!(mini.log, vec![Event::Visited(3), Event::Finished]);
+    }
+
+    #[test]
+    fn dies_to_murderer() {
+        let mut villagers: Vec<LivingVillager> = (1..=4)
+            .map(|i| Villager::new(VillagerType::Normal, i))
+            .collect();
+        villagers.push(Villager::new(VillagerType::Murderer, 5));
+        let mut village = Village::new_deterministic(villagers);
+
+        let mut mini = Mini::new(
+            1,
             vec![
-                Instruction::Operation(Operation::Increment),
-                Instruction::Operation(Operation::Increment),
+                Instruction::Action(Action::Visit),
+                Instruction::Operation(Operation::SetValue(Register::A, 5)),
             ],
             &village,
+        )
+        .unwrap();
+
+        mini.run_instruction(&mut village);
+        mini.run_instruction(&mut village);
+        assert_eq!(
+            mini.status(),
+            MiniStatus::Destroyed(DestroyReason::Murderer)
         );
+    }
+
+    #[test]
+    fn completes_correctly() {
+        let villagers: Vec<LivingVillager> = (1..=4)
+            .map(|i| Villager::new(VillagerType::Normal, i))
+            .collect();
+        let mut village = Village::new_deterministic(villagers);
+
+        let mut mini = Mini::new(
+            1,
+            vec![
+                Instruction::Operation(Operation::Increment(Register::A)),
+                Instruction::Break,
+            ],
+            &village,
+        )
+        .unwrap();
+
+        mini.run_instruction(&mut village);
+        assert_eq!(mini.status(), MiniStatus::Done);
+
+        let mut mini = Mini::new(
+            1,
+            vec![
+                Instruction::Operation(Operation::Increment(Register::A)),
+                Instruction::Operation(Operation::Increment(Register::A)),
+            ],
+            &village,
+        )
+        .unwrap();
 
         mini.run_instruction(&mut village);
         mini.run_instruction(&mut village);
         mini.run_instruction(&mut village);
-        assert_eq!(mini.status, MiniStatus::Done);
+        assert_eq!(mini.status(), MiniStatus::Done);
     }
 
     #[test]
@@ -444,23 +1622,26 @@ mod test {
             vec![
                 Instruction::Condition(
                     Condition::VillagerIsDead,
-                    vec![Instruction::Action(Action::PostRegister)],
+                    Rc::new(vec![Instruction::Action(Action::PostRegister)]),
                 ),
                 Instruction::Action(Action::Visit),
-                Instruction::Operation(Operation::SetValue(2)),
+                Instruction::Operation(Operation::SetValue(Register::A, 2)),
                 Instruction::Condition(
                     Condition::VillagerIsAlive,
-                    vec![Instruction::Action(Action::PostRegister)],
+                    Rc::new(vec![Instruction::Action(Action::PostRegister)]),
                 ),
             ],
             &village,
-        );
+        )
+        .unwrap();
 
         mini.run_until_completion(&mut village);
         assert_eq!(
             mini.log,
             vec![
+                Event::Visited(1),
                 Event::PostedRegister(0),
+                Event::Visited(2),
                 Event::PostedRegister(2),
                 Event::Finished
             ]
@@ -471,65 +1652,1576 @@ mod test {
             vec![
                 Instruction::Condition(
                     Condition::VillagerIsAlive,
-                    vec![Instruction::Action(Action::PostRegister)],
+                    Rc::new(vec![Instruction::Action(Action::PostRegister)]),
                 ),
                 Instruction::Action(Action::Visit),
-                Instruction::Operation(Operation::SetValue(2)),
+                Instruction::Operation(Operation::SetValue(Register::A, 2)),
                 Instruction::Condition(
                     Condition::VillagerIsDead,
-                    vec![Instruction::Action(Action::PostRegister)],
+                    Rc::new(vec![Instruction::Action(Action::PostRegister)]),
                 ),
             ],
             &village,
-        );
+        )
+        .unwrap();
 
         mini.run_until_completion(&mut village);
-        assert_eq!(mini.log, vec![Event::Finished]);
+        assert_eq!(
+            mini.log,
+            vec![Event::Visited(1), Event::Visited(2), Event::Finished]
+        );
     }
 
     #[test]
-    fn repeat() {
-        let mut village = Village::new_deterministic(vec![Villager::new(VillagerType::Normal, 1)]);
+    fn target_dead() {
+        let mut village = Village::new_deterministic(vec![
+            Villager::new(VillagerType::Normal, 1),
+            Villager::new(VillagerType::Normal, 2),
+            Villager::new(VillagerType::Normal, 3),
+        ]);
+        village.kill_villager(2).unwrap();
 
-        // keep posting the register until it's equal to 4
+        // the mini stays at villager 1 the whole time
         let mut mini = Mini::new(
             1,
-            vec![Instruction::Repeat(
-                u8::MAX,
-                vec![
-                    Instruction::Operation(Operation::Increment),
-                    Instruction::Action(Action::PostRegister),
-                    Instruction::Condition(Condition::RegisterEq(10), vec![Instruction::Break]),
-                ],
-            )],
+            vec![
+                Instruction::Condition(
+                    Condition::RegisterTargetDead,
+                    Rc::new(vec![Instruction::Action(Action::PostRegister)]),
+                ),
+                Instruction::Operation(Operation::SetValue(Register::A, 3)),
+                Instruction::Condition(
+                    Condition::RegisterTargetDead,
+                    Rc::new(vec![Instruction::Action(Action::PostRegister)]),
+                ),
+                Instruction::Operation(Operation::SetValue(Register::A, 2)),
+            ],
             &village,
-        );
+        )
+        .unwrap();
 
         mini.run_until_completion(&mut village);
+        assert_eq!(
+            mini.log,
+            vec![Event::Visited(1), Event::PostedRegister(2), Event::Finished]
+        );
 
-        // this also ensures break clears the rest of the active loop; if it didn't, 10 would be posted
-        let mut events: Vec<Event> = (0..=9).map(|i| Event::PostedRegister(i)).collect();
-        events.push(Event::Finished);
-        assert_eq!(mini.log, events);
+        // without introspection, the mini can't tell that villager 2 is dead
+        let config = MiniConfig {
+            sensors: Sensors {
+                introspection: false,
+                ..Default::default()
+            },
+            ..Default::default()
+        };
+        let mut mini = Mini::new_with_config(
+            1,
+            vec![
+                Instruction::Condition(
+                    Condition::RegisterTargetDead,
+                    Rc::new(vec![Instruction::Action(Action::PostRegister)]),
+                ),
+                Instruction::Operation(Operation::SetValue(Register::A, 2)),
+            ],
+            &village,
+            config,
+        )
+        .unwrap();
+        mini.run_until_completion(&mut village);
+        assert_eq!(mini.log, vec![Event::Visited(1), Event::Finished]);
     }
 
     #[test]
-    fn infinite_loop() {
+    fn disabled_sensors() {
         let mut village = Village::new_deterministic(vec![Villager::new(VillagerType::Normal, 1)]);
+        let config = MiniConfig {
+            sensors: Sensors {
+                villager_status: false,
+                ..Default::default()
+            },
+            ..Default::default()
+        };
 
-        // keep posting the register until it's equal to 4
-        let mut mini = Mini::new(
+        // the aliveness check shouldn't fire, but checking the register still should
+        let mut mini = Mini::new_with_config(
             1,
-            vec![Instruction::Repeat(
-                10, // this should usually be u8::Max, but this makes the test faster
-                vec![],
-            )],
+            vec![
+                Instruction::Condition(
+                    Condition::RegisterEq(0),
+                    Rc::new(vec![Instruction::Action(Action::PostRegister)]),
+                ),
+                Instruction::Condition(
+                    Condition::VillagerIsAlive,
+                    Rc::new(vec![Instruction::Action(Action::PostFlare)]),
+                ),
+            ],
             &village,
-        );
+            config,
+        )
+        .unwrap();
 
         mini.run_until_completion(&mut village);
-
-        // this also ensures break clears the rest of the active loop; if it didn't, 10 would be posted
-        assert!(mini.register < u8::MAX)
+        assert_eq!(
+            mini.log,
+            vec![Event::Visited(1), Event::PostedRegister(0), Event::Finished]
+        );
+    }
+
+    #[test]
+    fn stack_depth_history() {
+        let mut village = Village::new_deterministic(vec![Villager::new(VillagerType::Normal, 1)]);
+        let config = MiniConfig {
+            record_stack_depth: true,
+            ..Default::default()
+        };
+
+        let mut mini = Mini::new_with_config(
+            1,
+            vec![
+                Instruction::Condition(
+                    Condition::VillagerIsAlive,
+                    Rc::new(vec![
+                        Instruction::Action(Action::PostFlare),
+                        Instruction::Condition(
+                            Condition::VillagerIsAlive,
+                            Rc::new(vec![
+                                Instruction::Action(Action::PostFlare),
+                                Instruction::Action(Action::PostFlare),
+                            ]),
+                        ),
+                    ]),
+                ),
+                Instruction::Action(Action::PostRegister),
+            ],
+            &village,
+            config,
+        )
+        .unwrap();
+
+        mini.run_until_completion(&mut village);
+        assert_eq!(mini.stack_depth_history(), &[1, 2, 3, 2, 1, 0, 0]);
+
+        // nothing is recorded without the flag
+        let mut mini =
+            Mini::new(1, vec![Instruction::Action(Action::PostRegister)], &village).unwrap();
+        mini.run_until_completion(&mut village);
+        assert!(mini.stack_depth_history().is_empty());
+    }
+
+    #[test]
+    fn repeat() {
+        let mut village = Village::new_deterministic(vec![Villager::new(VillagerType::Normal, 1)]);
+
+        // keep posting the register until it's equal to 4
+        let mut mini = Mini::new(
+            1,
+            vec![Instruction::Repeat(
+                u8::MAX,
+                Rc::new(vec![
+                    Instruction::Operation(Operation::Increment(Register::A)),
+                    Instruction::Action(Action::PostRegister),
+                    Instruction::Condition(
+                        Condition::RegisterEq(10),
+                        Rc::new(vec![Instruction::Break]),
+                    ),
+                ]),
+            )],
+            &village,
+        )
+        .unwrap();
+
+        mini.run_until_completion(&mut village);
+
+        // this also ensures break clears the rest of the active loop; if it didn't, 10 would be posted
+        let mut events = vec![Event::Visited(1)];
+        events.extend((0..=9).map(Event::PostedRegister));
+        events.push(Event::Finished);
+        assert_eq!(mini.log, events);
+    }
+
+    #[test]
+    fn repeat_large_block() {
+        let mut village = Village::new_deterministic(vec![Villager::new(VillagerType::Normal, 1)]);
+
+        // every iteration counts up register b and back down again, so any instructions
+        // lost or duplicated between iterations would destroy the mini
+        let mut block = vec![Instruction::Operation(Operation::Decrement(Register::B)); 100];
+        block.extend(vec![
+            Instruction::Operation(Operation::Increment(
+                Register::B
+            ));
+            100
+        ]);
+        block.push(Instruction::Operation(Operation::Increment(Register::A)));
+        let mut mini = Mini::new(
+            1,
+            vec![
+                Instruction::Action(Action::PostRegister),
+                Instruction::Repeat(250, Rc::new(block)),
+            ],
+            &village,
+        )
+        .unwrap();
+
+        mini.run_until_completion(&mut village);
+
+        assert_eq!(
+            mini.log,
+            vec![
+                Event::Visited(1),
+                Event::PostedRegister(250),
+                Event::Finished
+            ]
+        );
+    }
+
+    #[test]
+    fn display_events() {
+        assert_eq!(Event::PostedRegister(4).to_string(), "posted register 4");
+        assert_eq!(Event::PostedFlare.to_string(), "posted flare");
+        assert_eq!(
+            Event::PostedNeighborDistance(Direction::Prev, None).to_string(),
+            "posted dist prev none"
+        );
+        assert_eq!(Event::Finished.to_string(), "finished");
+
+        assert_eq!(
+            MiniStatus::Destroyed(DestroyReason::Murderer).to_string(),
+            "destroyed by a murderer"
+        );
+        assert_eq!(MiniStatus::OutOfMoves.to_string(), "out of visits");
+    }
+
+    #[test]
+    fn display() {
+        assert_eq!(
+            Instruction::Condition(
+                Condition::RegisterEq(8),
+                Rc::new(vec![Instruction::Action(Action::PostFlare)])
+            )
+            .to_string(),
+            "if eq 8 { post flare }"
+        );
+
+        // remember that blocks are stored back to front
+        assert_eq!(
+            Instruction::Repeat(
+                u8::MAX,
+                Rc::new(vec![
+                    Instruction::Condition(
+                        Condition::VillagerIsDead,
+                        Rc::new(vec![Instruction::Break])
+                    ),
+                    Instruction::Operation(Operation::SetValue(Register::A, 3)),
+                    Instruction::Repeat(4, Rc::new(vec![])),
+                ])
+            )
+            .to_string(),
+            "repeat { repeat 4 {} set 3 if dead { break } }"
+        );
+    }
+
+    #[test]
+    fn step() {
+        let mut village = Village::new_deterministic(vec![Villager::new(VillagerType::Normal, 1)]);
+
+        let mut mini = Mini::new(
+            1,
+            vec![
+                Instruction::Operation(Operation::Decrement(Register::A)),
+                Instruction::Operation(Operation::Increment(Register::A)),
+                Instruction::Operation(Operation::Increment(Register::A)),
+                Instruction::Operation(Operation::SetValue(Register::A, 5)),
+            ],
+            &village,
+        )
+        .unwrap();
+
+        let mut registers = Vec::new();
+        while mini.stack_len() > 0 {
+            assert_eq!(mini.step(&mut village), MiniStatus::Running);
+            registers.push(mini.register());
+        }
+        assert_eq!(registers, vec![5, 6, 7, 6]);
+
+        // the mini only notices it's done once it tries to run another instruction,
+        // and stepping after that changes nothing
+        assert_eq!(mini.step(&mut village), MiniStatus::Done);
+        assert_eq!(mini.step(&mut village), MiniStatus::Done);
+        assert_eq!(mini.log, vec![Event::Visited(1), Event::Finished]);
+    }
+
+    #[test]
+    fn log_exports() {
+        let mut village = Village::new_deterministic(vec![
+            Villager::new(VillagerType::Normal, 1),
+            Villager::new(VillagerType::Normal, 3),
+        ]);
+
+        let mut mini = Mini::new(
+            1,
+            vec![
+                Instruction::Action(Action::PostNeighborDistance(Direction::Prev)),
+                Instruction::Action(Action::PostNeighborDistance(Direction::Next)),
+                Instruction::Action(Action::PostRegister),
+            ],
+            &village,
+        )
+        .unwrap();
+        mini.run_until_completion(&mut village);
+
+        assert_eq!(
+            mini.log_to_csv().lines().collect::<Vec<_>>(),
+            vec![
+                "index,event,direction,value",
+                "0,visited,,1",
+                "1,posted_register,,0",
+                "2,posted_neighbor_distance,next,2",
+                "3,posted_neighbor_distance,prev,",
+                "4,finished,,",
+            ]
+        );
+
+        #[cfg(feature = "serde")]
+        {
+            let json: serde_json::Value = serde_json::from_str(&mini.log_to_json()).unwrap();
+            let events = json.as_array().unwrap();
+            assert_eq!(events.len(), 5);
+            assert_eq!(events[0]["Visited"], 1);
+            assert_eq!(events[2]["PostedNeighborDistance"][0], "Next");
+            assert_eq!(events[4], "Finished");
+        }
+    }
+
+    #[test]
+    fn has_posted_flare() {
+        let mut village = Village::new_deterministic(vec![Villager::new(VillagerType::Normal, 1)]);
+
+        // only the check after the flare should post the register
+        let if_flared = Instruction::Condition(
+            Condition::HasPostedFlare,
+            Rc::new(vec![Instruction::Action(Action::PostRegister)]),
+        );
+        let mut mini = Mini::new(
+            1,
+            vec![
+                if_flared.clone(),
+                Instruction::Action(Action::PostFlare),
+                if_flared,
+            ],
+            &village,
+        )
+        .unwrap();
+
+        mini.run_until_completion(&mut village);
+        assert_eq!(
+            mini.log,
+            vec![
+                Event::Visited(1),
+                Event::PostedFlare,
+                Event::PostedRegister(0),
+                Event::Finished
+            ]
+        );
+    }
+
+    #[test]
+    fn flare_once() {
+        let mut village = Village::new_deterministic(vec![Villager::new(VillagerType::Normal, 1)]);
+
+        let mut mini = Mini::new(
+            1,
+            vec![Instruction::Repeat(
+                5,
+                Rc::new(vec![
+                    Instruction::Action(Action::PostRegister),
+                    Instruction::Action(Action::PostFlareOnce),
+                    Instruction::Operation(Operation::Increment(Register::A)),
+                ]),
+            )],
+            &village,
+        )
+        .unwrap();
+
+        mini.run_until_completion(&mut village);
+        assert_eq!(
+            mini.log,
+            vec![
+                Event::Visited(1),
+                Event::PostedFlare,
+                Event::PostedRegister(1),
+                Event::PostedRegister(2),
+                Event::PostedRegister(3),
+                Event::PostedRegister(4),
+                Event::PostedRegister(5),
+                Event::Finished
+            ]
+        );
+    }
+
+    #[test]
+    fn repeat_register() {
+        let mut village = Village::new_deterministic(vec![Villager::new(VillagerType::Normal, 1)]);
+
+        // changing the register inside the loop shouldn't change the number of iterations
+        let mut mini = Mini::new(
+            1,
+            vec![
+                Instruction::RepeatRegister(Rc::new(vec![
+                    Instruction::Operation(Operation::SetValue(Register::A, 10)),
+                    Instruction::Action(Action::PostFlare),
+                ])),
+                Instruction::Operation(Operation::SetValue(Register::A, 3)),
+            ],
+            &village,
+        )
+        .unwrap();
+
+        mini.run_until_completion(&mut village);
+        assert_eq!(
+            mini.log,
+            vec![
+                Event::Visited(1),
+                Event::PostedFlare,
+                Event::PostedFlare,
+                Event::PostedFlare,
+                Event::Finished
+            ]
+        );
+    }
+
+    #[test]
+    fn loop_counter() {
+        let mut village = Village::new_deterministic(vec![Villager::new(VillagerType::Normal, 1)]);
+
+        // the inner loop hasn't started yet when the counter is loaded,
+        // so the outer loop's counter should be the one loaded
+        let mut mini = Mini::new(
+            1,
+            vec![Instruction::Repeat(
+                5,
+                Rc::new(vec![
+                    Instruction::Repeat(2, Rc::new(vec![])),
+                    Instruction::Action(Action::PostRegister),
+                    Instruction::Operation(Operation::LoadLoopCounter),
+                ]),
+            )],
+            &village,
+        )
+        .unwrap();
+
+        mini.run_until_completion(&mut village);
+        let mut events = vec![Event::Visited(1)];
+        events.extend((0..=4).rev().map(Event::PostedRegister));
+        events.push(Event::Finished);
+        assert_eq!(mini.log, events);
+    }
+
+    #[test]
+    fn step_bounds() {
+        let loop_free = vec![
+            Instruction::Condition(
+                Condition::RegisterEq(1),
+                Rc::new(vec![Instruction::Action(Action::PostFlare)]),
+            ),
+            Instruction::Operation(Operation::Increment(Register::A)),
+            Instruction::Action(Action::PostRegister),
+        ];
+        assert_eq!(max_steps(&loop_free), Some(4));
+
+        let finite = vec![Instruction::Repeat(
+            3,
+            Rc::new(vec![
+                Instruction::Operation(Operation::Increment(Register::A)),
+                Instruction::Operation(Operation::Increment(Register::A)),
+            ]),
+        )];
+        assert_eq!(max_steps(&finite), Some(10));
+
+        let unbounded = vec![Instruction::Repeat(
+            3,
+            Rc::new(vec![Instruction::Repeat(
+                u8::MAX,
+                Rc::new(vec![Instruction::Break]),
+            )]),
+        )];
+        assert_eq!(max_steps(&unbounded), None);
+
+        // both bounded programs take every branch, so the bounds should be exact. the stack
+        // depth is also recorded when the mini notices it's out of instructions, hence the - 1
+        let mut village = Village::new_deterministic(vec![Villager::new(VillagerType::Normal, 1)]);
+        let config = MiniConfig {
+            record_stack_depth: true,
+            ..Default::default()
+        };
+        for program in [loop_free, finite] {
+            let bound = max_steps(&program);
+            let mut mini = Mini::new_with_config(1, program, &village, config).unwrap();
+            mini.run_until_completion(&mut village);
+            assert_eq!(Some(mini.stack_depth_history().len() as u64 - 1), bound);
+        }
+    }
+
+    #[test]
+    fn infinite_loop() {
+        let mut village = Village::new_deterministic(vec![Villager::new(VillagerType::Normal, 1)]);
+
+        // keep posting the register until it's equal to 4
+        let mut mini = Mini::new(
+            1,
+            vec![Instruction::Repeat(
+                10, // this should usually be u8::Max, but this makes the test faster
+                Rc::new(vec![]),
+            )],
+            &village,
+        )
+        .unwrap();
+
+        mini.run_until_completion(&mut village);
+
+        // this also ensures break clears the rest of the active loop; if it didn't, 10 would be posted
+        assert!(mini.register() < u8::MAX)
+    }
+
+    #[test]
+    fn checksum() {
+        let mut village = Village::new_deterministic(vec![Villager::new(VillagerType::Normal, 1)]);
+
+        let mut mini = Mini::new(
+            1,
+            vec![
+                Instruction::Action(Action::PostChecksum),
+                Instruction::Action(Action::PostRegister),
+                Instruction::Operation(Operation::SetValue(Register::A, 12)),
+                Instruction::Action(Action::PostRegister),
+                Instruction::Operation(Operation::SetValue(Register::A, 7)),
+                Instruction::Action(Action::PostRegister),
+                Instruction::Operation(Operation::SetValue(Register::A, 5)),
+                Instruction::Action(Action::PostChecksum),
+            ],
+            &village,
+        )
+        .unwrap();
+
+        mini.run_until_completion(&mut village);
+        assert_eq!(
+            mini.log,
+            vec![
+                Event::Visited(1),
+                Event::PostedChecksum(0),
+                Event::PostedRegister(5),
+                Event::PostedRegister(7),
+                Event::PostedRegister(12),
+                Event::PostedChecksum(5 ^ 7 ^ 12),
+                Event::Finished
+            ]
+        );
+    }
+
+    #[test]
+    fn visit_nth() {
+        let mut village = Village::new_deterministic(vec![
+            Villager::new(VillagerType::Normal, 7),
+            Villager::new(VillagerType::Normal, 2),
+            Villager::new(VillagerType::Normal, 4),
+            Villager::new(VillagerType::Normal, 9),
+        ]);
+        village.kill_villager(4).unwrap();
+
+        // living villagers in order are 2, 7, 9
+        let mut mini = Mini::new(
+            2,
+            vec![
+                Instruction::Action(Action::VisitNth),
+                Instruction::Operation(Operation::SetValue(Register::A, 1)),
+            ],
+            &village,
+        )
+        .unwrap();
+        mini.run_until_completion(&mut village);
+        assert_eq!(mini.status(), MiniStatus::Done);
+        assert_eq!(mini.location(), 7);
+
+        let mut mini = Mini::new(
+            2,
+            vec![
+                Instruction::Action(Action::VisitNth),
+                Instruction::Operation(Operation::SetValue(Register::A, 3)),
+            ],
+            &village,
+        )
+        .unwrap();
+        mini.run_until_completion(&mut village);
+        assert_eq!(mini.status(), MiniStatus::Lost);
+    }
+
+    #[test]
+    fn rank_programs() {
+        let village = Village::new_deterministic(vec![
+            Villager::new(VillagerType::Normal, 1),
+            Villager::new(VillagerType::Murderer, 2),
+            Villager::new(VillagerType::Normal, 3),
+        ]);
+
+        let programs = vec![
+            ("idle".to_string(), vec![]),
+            (
+                "detonator".to_string(),
+                vec![
+                    Instruction::Action(Action::Detonate),
+                    Instruction::Operation(Operation::SetValue(Register::A, 2)),
+                ],
+            ),
+        ];
+
+        let ranked = super::rank_programs(&programs, &village, 0);
+        assert_eq!(ranked[0].0, "detonator");
+        assert_eq!(
+            ranked[0].1,
+            Score {
+                murderers_eliminated: 1,
+                minis_lost: 0
+            }
+        );
+        assert_eq!(ranked[1].0, "idle");
+        assert_eq!(ranked[1].1.value(), 0);
+    }
+
+    #[test]
+    fn comparisons() {
+        let mut village = Village::new_deterministic(vec![Villager::new(VillagerType::Normal, 1)]);
+
+        // the register is 5, so gt posts the register for smaller values, lt
+        // posts a flare for larger values, and neither posts when it's equal
+        let program = |value: u8| {
+            vec![
+                Instruction::Condition(
+                    Condition::RegisterLess(value),
+                    Rc::new(vec![Instruction::Action(Action::PostFlare)]),
+                ),
+                Instruction::Condition(
+                    Condition::RegisterGreater(value),
+                    Rc::new(vec![Instruction::Action(Action::PostRegister)]),
+                ),
+                Instruction::Operation(Operation::SetValue(Register::A, 5)),
+            ]
+        };
+
+        let mut mini = Mini::new(1, program(4), &village).unwrap();
+        mini.run_until_completion(&mut village);
+        assert_eq!(
+            mini.log,
+            vec![Event::Visited(1), Event::PostedRegister(5), Event::Finished]
+        );
+
+        let mut mini = Mini::new(1, program(5), &village).unwrap();
+        mini.run_until_completion(&mut village);
+        assert_eq!(mini.log, vec![Event::Visited(1), Event::Finished]);
+
+        let mut mini = Mini::new(1, program(6), &village).unwrap();
+        mini.run_until_completion(&mut village);
+        assert_eq!(
+            mini.log,
+            vec![Event::Visited(1), Event::PostedFlare, Event::Finished]
+        );
+    }
+
+    #[test]
+    fn condition_else() {
+        let villagers: Vec<LivingVillager> = vec![
+            Villager::new(VillagerType::Normal, 1),
+            Villager::new(VillagerType::Normal, 2),
+        ];
+        let mut village = Village::new_deterministic(villagers);
+        village
+            .kill_villager(2)
+            .expect("we have a villager with id 2");
+
+        // posts a flare at villager 1, then breaks out of the loop at villager 2
+        let mut mini = Mini::new(
+            1,
+            vec![
+                Instruction::Action(Action::PostRegister),
+                Instruction::Repeat(
+                    u8::MAX,
+                    Rc::new(vec![
+                        Instruction::Action(Action::Visit),
+                        Instruction::Operation(Operation::Increment(Register::A)),
+                        Instruction::ConditionElse(
+                            Condition::VillagerIsAlive,
+                            Rc::new(vec![Instruction::Action(Action::PostFlare)]),
+                            Rc::new(vec![Instruction::Break]),
+                        ),
+                    ]),
+                ),
+                Instruction::Operation(Operation::SetValue(Register::A, 1)),
+            ],
+            &village,
+        )
+        .unwrap();
+
+        mini.run_until_completion(&mut village);
+        assert_eq!(
+            mini.log,
+            vec![
+                Event::Visited(1),
+                Event::PostedFlare,
+                Event::Visited(2),
+                Event::PostedRegister(2),
+                Event::Finished
+            ]
+        );
+    }
+
+    #[test]
+    fn repeat_count() {
+        let mut village = Village::new_deterministic(vec![Villager::new(VillagerType::Normal, 1)]);
+
+        let mut mini = Mini::new(
+            1,
+            vec![
+                Instruction::Action(Action::PostRegister),
+                Instruction::Repeat(
+                    3,
+                    Rc::new(vec![Instruction::Operation(Operation::Increment(
+                        Register::A,
+                    ))]),
+                ),
+            ],
+            &village,
+        )
+        .unwrap();
+
+        mini.run_until_completion(&mut village);
+        assert_eq!(
+            mini.log,
+            vec![Event::Visited(1), Event::PostedRegister(3), Event::Finished]
+        );
+    }
+
+    #[test]
+    fn register_b() {
+        let mut village = Village::new_deterministic(vec![Villager::new(VillagerType::Normal, 1)]);
+
+        let mut mini = Mini::new(
+            1,
+            vec![
+                Instruction::Action(Action::PostRegister),
+                Instruction::Operation(Operation::SwapRegisters),
+                Instruction::Action(Action::PostRegister),
+                Instruction::Operation(Operation::Decrement(Register::B)),
+                Instruction::Operation(Operation::Increment(Register::A)),
+                Instruction::Operation(Operation::SetValue(Register::B, 4)),
+                Instruction::Operation(Operation::SetValue(Register::A, 1)),
+            ],
+            &village,
+        )
+        .unwrap();
+        mini.run_until_completion(&mut village);
+        assert_eq!(
+            mini.log,
+            vec![
+                Event::Visited(1),
+                Event::PostedRegister(2),
+                Event::PostedRegister(3),
+                Event::Finished
+            ]
+        );
+
+        // overflow is checked for each register separately
+        let mut mini = Mini::new(
+            1,
+            vec![
+                Instruction::Operation(Operation::Increment(Register::B)),
+                Instruction::Operation(Operation::SetValue(Register::B, u8::MAX)),
+                Instruction::Operation(Operation::Decrement(Register::A)),
+                Instruction::Operation(Operation::Increment(Register::A)),
+            ],
+            &village,
+        )
+        .unwrap();
+        mini.run_until_completion(&mut village);
+        assert_eq!(
+            mini.status(),
+            MiniStatus::Destroyed(DestroyReason::Overflow)
+        );
+        assert_eq!(mini.register(), 0);
+
+        let mut mini = Mini::new(
+            1,
+            vec![Instruction::Operation(Operation::Decrement(Register::B))],
+            &village,
+        )
+        .unwrap();
+        mini.run_until_completion(&mut village);
+        assert_eq!(
+            mini.status(),
+            MiniStatus::Destroyed(DestroyReason::Underflow)
+        );
+    }
+
+    #[test]
+    fn register_stack() {
+        let mut village = Village::new_deterministic(vec![Villager::new(VillagerType::Normal, 1)]);
+
+        let mut mini = Mini::new(
+            1,
+            vec![
+                Instruction::Action(Action::PostRegister),
+                Instruction::Operation(Operation::PopRegister),
+                Instruction::Action(Action::PostRegister),
+                Instruction::Operation(Operation::SetValue(Register::A, 8)),
+                Instruction::Operation(Operation::PushRegister),
+                Instruction::Operation(Operation::SetValue(Register::A, 3)),
+            ],
+            &village,
+        )
+        .unwrap();
+        mini.run_until_completion(&mut village);
+        assert_eq!(
+            mini.log,
+            vec![
+                Event::Visited(1),
+                Event::PostedRegister(8),
+                Event::PostedRegister(3),
+                Event::Finished
+            ]
+        );
+
+        let mut mini = Mini::new(
+            1,
+            vec![Instruction::Operation(Operation::PopRegister)],
+            &village,
+        )
+        .unwrap();
+        mini.run_until_completion(&mut village);
+        assert_eq!(
+            mini.status(),
+            MiniStatus::Destroyed(DestroyReason::Underflow)
+        );
+    }
+
+    #[test]
+    fn arithmetic() {
+        let mut village = Village::new_deterministic(vec![Villager::new(VillagerType::Normal, 1)]);
+
+        let mut mini = Mini::new(
+            1,
+            vec![
+                Instruction::Action(Action::PostRegister),
+                Instruction::Operation(Operation::ModuloBy(4)),
+                Instruction::Action(Action::PostRegister),
+                Instruction::Operation(Operation::DivideBy(2)),
+                Instruction::Action(Action::PostRegister),
+                Instruction::Operation(Operation::MultiplyBy(3)),
+                Instruction::Operation(Operation::SetValue(Register::A, 5)),
+            ],
+            &village,
+        )
+        .unwrap();
+        mini.run_until_completion(&mut village);
+        assert_eq!(
+            mini.log,
+            vec![
+                Event::Visited(1),
+                Event::PostedRegister(15),
+                Event::PostedRegister(7),
+                Event::PostedRegister(3),
+                Event::Finished
+            ]
+        );
+
+        let mut mini = Mini::new(
+            1,
+            vec![
+                Instruction::Operation(Operation::DivideBy(0)),
+                Instruction::Operation(Operation::SetValue(Register::A, 5)),
+            ],
+            &village,
+        )
+        .unwrap();
+        mini.run_until_completion(&mut village);
+        assert_eq!(
+            mini.status(),
+            MiniStatus::Destroyed(DestroyReason::DivideByZero)
+        );
+
+        let mut mini = Mini::new(
+            1,
+            vec![
+                Instruction::Operation(Operation::MultiplyBy(2)),
+                Instruction::Operation(Operation::SetValue(Register::A, 128)),
+            ],
+            &village,
+        )
+        .unwrap();
+        mini.run_until_completion(&mut village);
+        assert_eq!(
+            mini.status(),
+            MiniStatus::Destroyed(DestroyReason::Overflow)
+        );
+    }
+
+    #[test]
+    fn add_sub_immediate() {
+        let mut village = Village::new_deterministic(vec![Villager::new(VillagerType::Normal, 1)]);
+
+        let mut mini = Mini::new(
+            1,
+            vec![
+                Instruction::Action(Action::PostRegister),
+                Instruction::Operation(Operation::SubImmediate(3)),
+                Instruction::Action(Action::PostRegister),
+                Instruction::Operation(Operation::AddImmediate(10)),
+                Instruction::Operation(Operation::SetValue(Register::A, 245)),
+            ],
+            &village,
+        )
+        .unwrap();
+        mini.run_until_completion(&mut village);
+        assert_eq!(
+            mini.log,
+            vec![
+                Event::Visited(1),
+                Event::PostedRegister(u8::MAX),
+                Event::PostedRegister(252),
+                Event::Finished
+            ]
+        );
+
+        let mut mini = Mini::new(
+            1,
+            vec![
+                Instruction::Operation(Operation::AddImmediate(11)),
+                Instruction::Operation(Operation::SetValue(Register::A, 245)),
+            ],
+            &village,
+        )
+        .unwrap();
+        mini.run_until_completion(&mut village);
+        assert_eq!(
+            mini.status(),
+            MiniStatus::Destroyed(DestroyReason::Overflow)
+        );
+
+        let mut mini = Mini::new(
+            1,
+            vec![Instruction::Operation(Operation::SubImmediate(1))],
+            &village,
+        )
+        .unwrap();
+        mini.run_until_completion(&mut village);
+        assert_eq!(
+            mini.status(),
+            MiniStatus::Destroyed(DestroyReason::Underflow)
+        );
+    }
+
+    #[test]
+    fn break_if() {
+        let mut village = Village::new_deterministic(vec![Villager::new(VillagerType::Normal, 1)]);
+
+        // the same as the repeat test, but with break if
+        let mut mini = Mini::new(
+            1,
+            vec![Instruction::Repeat(
+                u8::MAX,
+                Rc::new(vec![
+                    Instruction::Operation(Operation::Increment(Register::A)),
+                    Instruction::Action(Action::PostRegister),
+                    Instruction::BreakIf(Condition::RegisterEq(10)),
+                ]),
+            )],
+            &village,
+        )
+        .unwrap();
+
+        mini.run_until_completion(&mut village);
+
+        let mut events = vec![Event::Visited(1)];
+        events.extend((0..=9).map(Event::PostedRegister));
+        events.push(Event::Finished);
+        assert_eq!(mini.log, events);
+    }
+
+    #[test]
+    fn continue_loop() {
+        let mut village = Village::new_deterministic(vec![Villager::new(VillagerType::Normal, 1)]);
+
+        // posts every value but 2
+        let mut mini = Mini::new(
+            1,
+            vec![Instruction::Repeat(
+                3,
+                Rc::new(vec![
+                    Instruction::Action(Action::PostRegister),
+                    Instruction::Condition(
+                        Condition::RegisterEq(2),
+                        Rc::new(vec![Instruction::Continue]),
+                    ),
+                    Instruction::Operation(Operation::Increment(Register::A)),
+                ]),
+            )],
+            &village,
+        )
+        .unwrap();
+
+        mini.run_until_completion(&mut village);
+        assert_eq!(
+            mini.log,
+            vec![
+                Event::Visited(1),
+                Event::PostedRegister(1),
+                Event::PostedRegister(3),
+                Event::Finished
+            ]
+        );
+    }
+
+    #[test]
+    fn instruction_budget() {
+        let mut village = Village::new_deterministic(vec![Villager::new(VillagerType::Normal, 1)]);
+
+        // without a budget this would run for more than u8::MAX^3 steps
+        let program = vec![Instruction::Repeat(
+            u8::MAX,
+            Rc::new(vec![Instruction::Repeat(
+                u8::MAX,
+                Rc::new(vec![Instruction::Repeat(
+                    u8::MAX,
+                    Rc::new(vec![Instruction::Operation(Operation::SetValue(
+                        Register::A,
+                        1,
+                    ))]),
+                )]),
+            )]),
+        )];
+        let config = MiniConfig {
+            instruction_budget: Some(1000),
+            ..Default::default()
+        };
+
+        let mut mini = Mini::new_with_config(1, program, &village, config).unwrap();
+        mini.run_until_completion(&mut village);
+        assert_eq!(mini.status(), MiniStatus::OutOfTime);
+        assert_eq!(mini.steps, 1000);
+
+        // finishing right at the budget is fine
+        let config = MiniConfig {
+            instruction_budget: Some(1),
+            ..Default::default()
+        };
+        let mut mini = Mini::new_with_config(
+            1,
+            vec![Instruction::Operation(Operation::SetValue(Register::A, 1))],
+            &village,
+            config,
+        )
+        .unwrap();
+        mini.run_until_completion(&mut village);
+        assert_eq!(mini.status(), MiniStatus::Done);
+    }
+
+    #[test]
+    fn read_location() {
+        let villagers: Vec<LivingVillager> = (1..=3)
+            .map(|i| Villager::new(VillagerType::Normal, i))
+            .collect();
+        let mut village = Village::new_deterministic(villagers);
+
+        let mut mini = Mini::new(
+            1,
+            vec![
+                Instruction::Action(Action::PostRegister),
+                Instruction::Action(Action::ReadLocation),
+                Instruction::Operation(Operation::SetValue(Register::A, 0)),
+                Instruction::Action(Action::Visit),
+                Instruction::Operation(Operation::SetValue(Register::A, 3)),
+            ],
+            &village,
+        )
+        .unwrap();
+
+        mini.run_until_completion(&mut village);
+        assert_eq!(
+            mini.log,
+            vec![
+                Event::Visited(1),
+                Event::Visited(3),
+                Event::PostedRegister(3),
+                Event::Finished
+            ]
+        );
+    }
+
+    #[test]
+    fn visit_up_down() {
+        let villagers: Vec<LivingVillager> = (1..=4)
+            .map(|i| Villager::new(VillagerType::Normal, i))
+            .collect();
+        let mut village = Village::new_deterministic(villagers);
+        village.kill_villager(2).unwrap();
+
+        // up from 1 skips the dead villager at 2, and down from 3 skips it too
+        let mut mini = Mini::new(
+            1,
+            vec![
+                Instruction::Action(Action::PostRegister),
+                Instruction::Action(Action::ReadLocation),
+                Instruction::Action(Action::VisitDown),
+                Instruction::Action(Action::PostRegister),
+                Instruction::Action(Action::ReadLocation),
+                Instruction::Action(Action::VisitUp),
+            ],
+            &village,
+        )
+        .unwrap();
+        mini.run_until_completion(&mut village);
+        assert_eq!(
+            mini.log,
+            vec![
+                Event::Visited(1),
+                Event::Visited(3),
+                Event::PostedRegister(3),
+                Event::Visited(1),
+                Event::PostedRegister(1),
+                Event::Finished
+            ]
+        );
+
+        // there's nobody below 1
+        let mut mini =
+            Mini::new(1, vec![Instruction::Action(Action::VisitDown)], &village).unwrap();
+        mini.run_until_completion(&mut village);
+        assert_eq!(mini.status(), MiniStatus::Lost);
+    }
+
+    #[test]
+    fn return_visit() {
+        let villagers: Vec<LivingVillager> = (1..=3)
+            .map(|i| Villager::new(VillagerType::Normal, i))
+            .collect();
+        let mut village = Village::new_deterministic(villagers);
+
+        let mut mini = Mini::new(
+            1,
+            vec![
+                Instruction::Action(Action::Return),
+                Instruction::Action(Action::Visit),
+                Instruction::Operation(Operation::SetValue(Register::A, 3)),
+            ],
+            &village,
+        )
+        .unwrap();
+        mini.run_until_completion(&mut village);
+        assert_eq!(mini.status(), MiniStatus::Done);
+        assert_eq!(mini.location(), 1);
+
+        // returning without having moved does nothing
+        let mut mini = Mini::new(1, vec![Instruction::Action(Action::Return)], &village).unwrap();
+        mini.run_until_completion(&mut village);
+        assert_eq!(mini.status(), MiniStatus::Done);
+        assert_eq!(mini.location(), 1);
+
+        // returning to a villager who has died since is like visiting any dead villager
+        let mut mini = Mini::new(
+            1,
+            vec![
+                Instruction::Action(Action::Return),
+                Instruction::Action(Action::Visit),
+                Instruction::Operation(Operation::SetValue(Register::A, 3)),
+            ],
+            &village,
+        )
+        .unwrap();
+        mini.step(&mut village);
+        mini.step(&mut village);
+        village.kill_villager(1).unwrap();
+        mini.run_until_completion(&mut village);
+        assert_eq!(mini.status(), MiniStatus::Done);
+        assert_eq!(mini.location(), 1);
+        assert_eq!(
+            mini.log,
+            vec![
+                Event::Visited(1),
+                Event::Visited(3),
+                Event::Visited(1),
+                Event::Finished
+            ]
+        );
+    }
+
+    #[test]
+    fn detonation_events() {
+        let villagers: Vec<LivingVillager> = (1..=2)
+            .map(|i| Villager::new(VillagerType::Normal, i))
+            .collect();
+        let mut village = Village::new_deterministic(villagers);
+
+        let mut mini = Mini::new(
+            1,
+            vec![
+                Instruction::Action(Action::Detonate),
+                Instruction::Operation(Operation::SetValue(Register::A, 2)),
+            ],
+            &village,
+        )
+        .unwrap();
+        mini.run_until_completion(&mut village);
+        assert!(village.dead_villager(2).is_some());
+        assert_eq!(mini.log.last(), Some(&Event::Detonated(2)));
+
+        // there's nobody left at 2 to kill
+        let mut mini = Mini::new(
+            1,
+            vec![
+                Instruction::Action(Action::Detonate),
+                Instruction::Operation(Operation::SetValue(Register::A, 2)),
+            ],
+            &village,
+        )
+        .unwrap();
+        mini.run_until_completion(&mut village);
+        assert_eq!(
+            mini.status(),
+            MiniStatus::Destroyed(DestroyReason::SelfDetonate)
+        );
+        assert_eq!(mini.log.last(), Some(&Event::Fizzled(2)));
+    }
+
+    #[test]
+    fn dies_to_afraid() {
+        let mut village = Village::new_deterministic(vec![
+            Villager::new(VillagerType::Normal, 1),
+            Villager::new(VillagerType::Afraid, 2),
+        ]);
+
+        let mut mini = Mini::new(
+            1,
+            vec![
+                Instruction::Action(Action::Visit),
+                Instruction::Operation(Operation::SetValue(Register::A, 2)),
+            ],
+            &village,
+        )
+        .unwrap();
+        mini.run_until_completion(&mut village);
+        assert_eq!(mini.status(), MiniStatus::Destroyed(DestroyReason::Afraid));
+        // unlike murderers, afraid villagers don't clear the log
+        assert_eq!(mini.log, vec![Event::Visited(1), Event::Visited(2)]);
+    }
+
+    #[test]
+    fn noop() {
+        let villagers: Vec<LivingVillager> = (1..=2)
+            .map(|i| Villager::new(VillagerType::Normal, i))
+            .collect();
+        let mut village = Village::new_deterministic(villagers);
+
+        let mut mini = Mini::new(
+            2,
+            vec![
+                Instruction::Noop,
+                Instruction::Operation(Operation::SetValue(Register::A, 7)),
+            ],
+            &village,
+        )
+        .unwrap();
+        mini.run_instruction(&mut village);
+        mini.run_instruction(&mut village);
+        assert_eq!(mini.register(), 7);
+        assert_eq!(mini.location(), 2);
+        assert_eq!(mini.status(), MiniStatus::Running);
+        assert_eq!(mini.steps, 2);
+    }
+
+    #[test]
+    fn detonate_at() {
+        let villagers: Vec<LivingVillager> = (1..=4)
+            .map(|i| Villager::new(VillagerType::Normal, i))
+            .collect();
+        let mut village = Village::new_deterministic(villagers);
+
+        let mut mini = Mini::new(
+            1,
+            vec![
+                Instruction::Action(Action::DetonateAt(4)),
+                Instruction::Operation(Operation::SetValue(Register::A, 2)),
+            ],
+            &village,
+        )
+        .unwrap();
+        mini.run_until_completion(&mut village);
+        assert!(village.dead_villager(4).is_some());
+        assert!(village.living_villager(2).is_some());
+        assert_eq!(mini.register(), 2);
+
+        // the bare form still uses the register
+        let mut mini = Mini::new(
+            1,
+            vec![
+                Instruction::Action(Action::Detonate),
+                Instruction::Operation(Operation::SetValue(Register::A, 2)),
+            ],
+            &village,
+        )
+        .unwrap();
+        mini.run_until_completion(&mut village);
+        assert!(village.dead_villager(2).is_some());
+    }
+
+    #[test]
+    fn randomize() {
+        let program = vec![
+            Instruction::Action(Action::PostRegister),
+            Instruction::Action(Action::Randomize(8)),
+        ];
+
+        let mut village = Village::new_deterministic(vec![Villager::new(VillagerType::Normal, 1)]);
+        village.reseed(5);
+        let mut mini = Mini::new(1, program, &village).unwrap();
+        mini.run_until_completion(&mut village);
+        assert_eq!(
+            mini.log,
+            vec![Event::Visited(1), Event::PostedRegister(1), Event::Finished]
+        );
+    }
+
+    #[test]
+    fn parity() {
+        let mut village = Village::new_deterministic(vec![Villager::new(VillagerType::Normal, 1)]);
+
+        let program = |value: u8| {
+            vec![
+                Instruction::Condition(
+                    Condition::RegisterOdd,
+                    Rc::new(vec![Instruction::Action(Action::PostFlare)]),
+                ),
+                Instruction::Condition(
+                    Condition::RegisterEven,
+                    Rc::new(vec![Instruction::Action(Action::PostRegister)]),
+                ),
+                Instruction::Operation(Operation::SetValue(Register::A, value)),
+            ]
+        };
+
+        let mut mini = Mini::new(1, program(4), &village).unwrap();
+        mini.run_until_completion(&mut village);
+        assert_eq!(
+            mini.log,
+            vec![Event::Visited(1), Event::PostedRegister(4), Event::Finished]
+        );
+
+        let mut mini = Mini::new(1, program(7), &village).unwrap();
+        mini.run_until_completion(&mut village);
+        assert_eq!(
+            mini.log,
+            vec![Event::Visited(1), Event::PostedFlare, Event::Finished]
+        );
+    }
+
+    #[test]
+    fn label_conditions() {
+        let villagers: Vec<LivingVillager> = (1..=2)
+            .map(|i| Villager::new(VillagerType::Normal, i))
+            .collect();
+        let mut village = Village::new_deterministic(villagers);
+        village.kill_villager(2).unwrap();
+
+        // posts a flare if the first block runs and the register if the else block does
+        let program = |condition: Condition| {
+            vec![Instruction::ConditionElse(
+                condition,
+                Rc::new(vec![Instruction::Action(Action::PostFlare)]),
+                Rc::new(vec![Instruction::Action(Action::PostRegister)]),
+            )]
+        };
+        let mut run = |condition: Condition| {
+            let mut mini = Mini::new(1, program(condition), &village).unwrap();
+            mini.run_until_completion(&mut village);
+            mini.log
+        };
+
+        let flare = vec![Event::Visited(1), Event::PostedFlare, Event::Finished];
+        let register = vec![Event::Visited(1), Event::PostedRegister(0), Event::Finished];
+        let neither = vec![Event::Visited(1), Event::Finished];
+
+        assert_eq!(run(Condition::LabelAlive(1)), flare);
+        assert_eq!(run(Condition::LabelDead(1)), register);
+        assert_eq!(run(Condition::LabelAlive(2)), register);
+        assert_eq!(run(Condition::LabelDead(2)), flare);
+        assert_eq!(run(Condition::LabelAlive(3)), neither);
+        assert_eq!(run(Condition::LabelDead(3)), neither);
+    }
+
+    #[test]
+    fn count_living() {
+        let villagers: Vec<LivingVillager> = (1..=5)
+            .map(|i| Villager::new(VillagerType::Normal, i))
+            .collect();
+        let mut village = Village::new_deterministic(villagers);
+        village.kill_villager(3).unwrap();
+
+        let mut mini = Mini::new(
+            1,
+            vec![
+                Instruction::Action(Action::PostRegister),
+                Instruction::Action(Action::CountLiving),
+            ],
+            &village,
+        )
+        .unwrap();
+        mini.run_until_completion(&mut village);
+        assert_eq!(
+            mini.log,
+            vec![Event::Visited(1), Event::PostedRegister(4), Event::Finished]
+        );
+
+        // without introspection, the register is left alone
+        let config = MiniConfig {
+            sensors: Sensors {
+                introspection: false,
+                ..Default::default()
+            },
+            ..Default::default()
+        };
+        let mut mini = Mini::new_with_config(
+            1,
+            vec![
+                Instruction::Action(Action::PostRegister),
+                Instruction::Action(Action::CountLiving),
+            ],
+            &village,
+            config,
+        )
+        .unwrap();
+        mini.run_until_completion(&mut village);
+        assert_eq!(
+            mini.log,
+            vec![Event::Visited(1), Event::PostedRegister(0), Event::Finished]
+        );
+    }
+
+    #[test]
+    fn while_loop() {
+        let mut village = Village::new_deterministic(vec![Villager::new(VillagerType::Normal, 1)]);
+
+        // while lt 3 { incr post register }
+        let mut mini = Mini::new(
+            1,
+            vec![Instruction::Repeat(
+                u8::MAX,
+                Rc::new(vec![Instruction::ConditionElse(
+                    Condition::RegisterLess(3),
+                    Rc::new(vec![
+                        Instruction::Action(Action::PostRegister),
+                        Instruction::Operation(Operation::Increment(Register::A)),
+                    ]),
+                    Rc::new(vec![Instruction::Break]),
+                )]),
+            )],
+            &village,
+        )
+        .unwrap();
+        mini.run_until_completion(&mut village);
+
+        assert_eq!(
+            mini.log,
+            vec![
+                Event::Visited(1),
+                Event::PostedRegister(1),
+                Event::PostedRegister(2),
+                Event::PostedRegister(3),
+                Event::Finished,
+            ]
+        );
+    }
+
+    #[test]
+    fn break_to() {
+        let mut village = Village::new_deterministic(vec![Villager::new(VillagerType::Normal, 1)]);
+
+        // repeat 'outer { incr repeat { post register break 'outer } }
+        let mut mini = Mini::new(
+            1,
+            vec![
+                Instruction::Action(Action::PostFlare),
+                Instruction::LabeledRepeat(
+                    "outer".to_string(),
+                    u8::MAX,
+                    Rc::new(vec![
+                        Instruction::Repeat(
+                            u8::MAX,
+                            Rc::new(vec![
+                                Instruction::BreakTo("outer".to_string()),
+                                Instruction::Action(Action::PostRegister),
+                            ]),
+                        ),
+                        Instruction::Operation(Operation::Increment(Register::A)),
+                    ]),
+                ),
+            ],
+            &village,
+        )
+        .unwrap();
+        mini.run_until_completion(&mut village);
+
+        // both loops ended after one iteration, but the program kept going after them
+        assert_eq!(
+            mini.log,
+            vec![
+                Event::Visited(1),
+                Event::PostedRegister(1),
+                Event::PostedFlare,
+                Event::Finished,
+            ]
+        );
+    }
+
+    #[cfg(feature = "serde")]
+    #[test]
+    fn json_round_trip() {
+        let program = vec![
+            Instruction::Repeat(
+                3,
+                Rc::new(vec![
+                    Instruction::ConditionElse(
+                        Condition::RegisterEq(2),
+                        Rc::new(vec![Instruction::Action(Action::PostNeighborDistance(
+                            Direction::Next,
+                        ))]),
+                        Rc::new(vec![Instruction::BreakTo("outer".to_string())]),
+                    ),
+                    Instruction::Operation(Operation::SetValue(Register::B, 7)),
+                ]),
+            ),
+            Instruction::Action(Action::Visit),
+        ];
+
+        let json = serde_json::to_string(&program).unwrap();
+        assert_eq!(
+            serde_json::from_str::<Vec<Instruction>>(&json).unwrap(),
+            program
+        );
+    }
+
+    #[test]
+    fn starting_location() {
+        let village = Village::new_deterministic(vec![
+            Villager::new(VillagerType::Normal, 1),
+            Villager::new(VillagerType::Murderer, 2),
+        ]);
+
+        assert_eq!(
+            Mini::new(3, vec![], &village).err(),
+            Some(MiniError::NoSuchStartingVillager(3))
+        );
+
+        // starting at a murderer is allowed, it just doesn't end well
+        let mini = Mini::new(2, vec![], &village).unwrap();
+        assert_eq!(
+            mini.status(),
+            MiniStatus::Destroyed(DestroyReason::Murderer)
+        );
     }
 }
@@ -1,18 +1,137 @@
-use std::{ffi::OsStr, fs::File, io::Read, path::PathBuf};
+use std::{collections::HashMap, ffi::OsStr, fs::File, io::Read, path::PathBuf, rc::Rc};
 
 use ariadne::{Color, Label, Report, ReportKind, Source};
 use chumsky::{
-    extra::Err,
+    extra::Full,
+    input::{Checkpoint, Cursor},
+    inspector::Inspector,
     prelude::*,
     text::{inline_whitespace, whitespace},
 };
 use thiserror::Error;
 
-use crate::mini::{Action, Condition, Instruction, Instructions, Operation};
+use crate::{
+    mini::{Action, Condition, Instruction, Instructions, Operation, Register},
+    village::Direction,
+};
+
+/// a block made with `define`
+struct Defined {
+    /// the block's instructions, in written order
+    instructions: Instructions,
+    /// how many instructions calling the block adds, counting nested ones
+    size: usize,
+    /// how deeply blocks are nested inside the block
+    depth: usize,
+}
+
+/// blocks made with `define`, which the parser keeps track of while it runs
+struct Definitions {
+    /// every block defined so far
+    blocks: HashMap<String, Defined>,
+    /// the blocks currently being defined, innermost last, along with how
+    /// many blocks were open when each one started
+    defining: Vec<(String, usize)>,
+    /// how many instructions calls have added so far, counting nested ones
+    inlined: usize,
+    /// how many blocks are being parsed right now, including the top level. nesting
+    /// is checked before calls are inlined, so this is used to check that calls don't
+    /// nest blocks too deeply
+    open_blocks: usize,
+    max_depth: usize,
+    /// the byte offset of the first call which nested blocks too deeply
+    too_deep_at: Option<usize>,
+}
+
+impl Definitions {
+    fn new(max_depth: usize) -> Self {
+        Self {
+            blocks: HashMap::new(),
+            defining: Vec::new(),
+            inlined: 0,
+            open_blocks: 0,
+            max_depth,
+            too_deep_at: None,
+        }
+    }
+}
+
+impl Default for Definitions {
+    fn default() -> Self {
+        Self::new(MAX_NESTING_DEPTH)
+    }
+}
+
+// chumsky needs parser state to be told about backtracking. definitions don't need
+// to be undone, since a partly parsed define always makes the whole parse fail
+impl<'a> Inspector<'a, &'a str> for Definitions {
+    type Checkpoint = ();
+    fn on_token(&mut self, _: &char) {}
+    fn on_save<'parse>(&self, _: &Cursor<'a, 'parse, &'a str>) {}
+    fn on_rewind<'parse>(&mut self, _: &Checkpoint<'a, 'parse, &'a str, ()>) {}
+}
+
+type Extra<'a> = Full<Rich<'a, char>, Definitions, ()>;
+
+/// how deeply blocks can be nested by default. the parser recurses into every block,
+/// so without a limit, pathologically nested code would overflow the stack. no sensible
+/// program comes anywhere near this, and it's low enough to be safe on the main thread
+/// even in debug builds (where each level of nesting takes a lot of stack)
+pub const MAX_NESTING_DEPTH: usize = 32;
+
+/// the most instructions calls can add to a program. blocks can call other blocks
+/// several times, so without a limit, a short program could grow exponentially
+const MAX_INLINED_INSTRUCTIONS: usize = 100_000;
+
+/// how deeply blocks are nested inside a block (0 if none of its instructions have blocks)
+fn nesting_depth(instructions: &Instructions) -> usize {
+    instructions
+        .iter()
+        .map(|instruction| match instruction {
+            Instruction::Condition(_, block)
+            | Instruction::Repeat(_, block)
+            | Instruction::LabeledRepeat(_, _, block)
+            | Instruction::RepeatRegister(block) => 1 + nesting_depth(block),
+            Instruction::ConditionElse(_, block, else_block) => {
+                1 + nesting_depth(block).max(nesting_depth(else_block))
+            }
+            _ => 0,
+        })
+        .max()
+        .unwrap_or(0)
+}
+
+/// the number of instructions in a block, counting the ones nested inside others
+fn instruction_count(instructions: &Instructions) -> usize {
+    instructions
+        .iter()
+        .map(|instruction| {
+            1 + match instruction {
+                Instruction::Condition(_, block)
+                | Instruction::Repeat(_, block)
+                | Instruction::LabeledRepeat(_, _, block)
+                | Instruction::RepeatRegister(block) => instruction_count(block),
+                Instruction::ConditionElse(_, block, else_block) => {
+                    instruction_count(block) + instruction_count(else_block)
+                }
+                _ => 0,
+            }
+        })
+        .sum()
+}
+
+/// a parser for anything that can go between instructions: whitespace and
+/// comments, which run from a `#` to the end of the line
+fn padding<'a>() -> impl Parser<'a, &'a str, (), Extra<'a>> + Clone {
+    let comment = just('#').then(none_of('\n').repeated());
+    whitespace()
+        .then(comment.then(whitespace()).repeated())
+        .ignored()
+}
 
 /// a function which returns an instruction parser. should
 /// be used as instructions().parse()
-fn instructions<'a>() -> impl Parser<'a, &'a str, Instructions, Err<Rich<'a, char>>> {
+fn instructions<'a>() -> impl Parser<'a, &'a str, Instructions, Extra<'a>> {
     // because I only have like 15 hours to spend on this, I decided against writing my own
     // parser. a little bit of research let me to the chumsky crate you see here,
     // but in retrospect, I should have used something different; this code is a bit
@@ -26,75 +145,401 @@ fn instructions<'a>() -> impl Parser<'a, &'a str, Instructions, Err<Rich<'a, cha
     // so we have to use recursive()
     recursive(|instructions_block| {
         // parser for u8s. will give an error for ints which are not u8s.
-        let byte = text::int::<_, Err<Rich<char>>>(10).try_map(|s: &str, span| {
+        let byte = text::int::<_, Extra>(10).try_map(|s: &str, span| {
             s.parse::<u8>()
                 .map_err(|e| Rich::custom(span, format!("Invalid u8: {}", e)))
         });
 
+        // parser for registers. a register name can't run into the word after it,
+        // so `incr break` isn't read as register b followed by `reak`
+        let register = choice((just('a').to(Register::A), just('b').to(Register::B))).then_ignore(
+            any()
+                .filter(|c: &char| c.is_alphanumeric() || *c == '_')
+                .not(),
+        );
+
+        // parser for an optional register, defaulting to register a
+        let register_or_a = inline_whitespace()
+            .ignore_then(register)
+            .or_not()
+            .map(|register| register.unwrap_or(Register::A));
+
+        // parser for loop labels, like 'outer
+        let loop_label = just('\'').ignore_then(text::ident().map(|label: &str| label.to_string()));
+
+        // parser for directions
+        let direction = choice((
+            just("next").to(Direction::Next),
+            just("prev").to(Direction::Prev),
+        ));
+
         // action parser. returns an Instruction.
         let action = choice((
             just("post")
                 .then(inline_whitespace())
                 .then(just("register"))
                 .to(Action::PostRegister),
+            just("post")
+                .then(inline_whitespace())
+                .then(just("flare"))
+                .then(inline_whitespace())
+                .then(just("once"))
+                .to(Action::PostFlareOnce),
             just("post")
                 .then(inline_whitespace())
                 .then(just("flare"))
                 .to(Action::PostFlare),
+            just("post")
+                .then(inline_whitespace())
+                .then(just("checksum"))
+                .to(Action::PostChecksum),
+            just("post")
+                .then(inline_whitespace())
+                .then(just("dist"))
+                .then(inline_whitespace())
+                .ignore_then(direction)
+                .map(Action::PostNeighborDistance),
+            just("detonate")
+                .then(inline_whitespace())
+                .then(just("if"))
+                .then(inline_whitespace())
+                .then(just("valid"))
+                .to(Action::DetonateIfValid),
+            just("detonate")
+                .then(inline_whitespace())
+                .ignore_then(byte)
+                .map(Action::DetonateAt),
             just("detonate").to(Action::Detonate),
+            just("visit")
+                .then(inline_whitespace())
+                .then(just("nth"))
+                .to(Action::VisitNth),
             just("visit").to(Action::Visit),
+            just("whereami").to(Action::ReadLocation),
+            just("count").to(Action::CountLiving),
+            just("rand")
+                .then(inline_whitespace())
+                .ignore_then(byte)
+                .map(Action::Randomize),
+            just("up").to(Action::VisitUp),
+            just("down").to(Action::VisitDown),
+            just("back").to(Action::Return),
+            just("checkpoint")
+                .then(inline_whitespace())
+                .ignore_then(byte)
+                .map(Action::Checkpoint),
         ))
         .map(Instruction::Action);
 
         // operation parser. returns an Instruction.
         let operation = choice((
-            just("incr").to(Operation::Increment),
-            just("decr").to(Operation::Decrement),
+            just("incr")
+                .ignore_then(register_or_a)
+                .map(Operation::Increment),
+            just("decr")
+                .ignore_then(register_or_a)
+                .map(Operation::Decrement),
             just("set")
+                .ignore_then(register_or_a)
+                .then_ignore(inline_whitespace())
+                .then(byte)
+                .map(|(register, value)| Operation::SetValue(register, value)),
+            just("rotl")
+                .then(inline_whitespace())
+                .ignore_then(byte)
+                .map(Operation::RotateLeft),
+            just("rotr")
+                .then(inline_whitespace())
+                .ignore_then(byte)
+                .map(Operation::RotateRight),
+            just("absdiff")
+                .then(inline_whitespace())
+                .ignore_then(byte)
+                .map(Operation::AbsDiff),
+            just("add")
+                .then(inline_whitespace())
+                .ignore_then(byte)
+                .map(Operation::AddImmediate),
+            just("sub")
                 .then(inline_whitespace())
                 .ignore_then(byte)
-                .map(Operation::SetValue),
+                .map(Operation::SubImmediate),
+            just("mul")
+                .then(inline_whitespace())
+                .ignore_then(byte)
+                .map(Operation::MultiplyBy),
+            just("div")
+                .then(inline_whitespace())
+                .ignore_then(byte)
+                .map(Operation::DivideBy),
+            just("mod")
+                .then(inline_whitespace())
+                .ignore_then(byte)
+                .map(Operation::ModuloBy),
+            just("load")
+                .then(inline_whitespace())
+                .then(just("loop"))
+                .to(Operation::LoadLoopCounter),
+            just("swap")
+                .then(inline_whitespace())
+                .then(just("loc"))
+                .to(Operation::SwapRegisterLocation),
+            just("swap").to(Operation::SwapRegisters),
+            just("push").to(Operation::PushRegister),
+            just("pop").to(Operation::PopRegister),
         ))
         .map(Instruction::Operation);
 
+        // parser for the condition being checked by conditionals. returns a Condition.
+        let condition_kind = choice((
+            just("alive")
+                .then(inline_whitespace())
+                .ignore_then(byte)
+                .map(Condition::LabelAlive),
+            just("dead")
+                .then(inline_whitespace())
+                .ignore_then(byte)
+                .map(Condition::LabelDead),
+            just("alive").to(Condition::VillagerIsAlive),
+            just("dead").to(Condition::VillagerIsDead),
+            just("eq")
+                .then(inline_whitespace())
+                .ignore_then(byte)
+                .map(Condition::RegisterEq),
+            just("gt")
+                .then(inline_whitespace())
+                .ignore_then(byte)
+                .map(Condition::RegisterGreater),
+            just("lt")
+                .then(inline_whitespace())
+                .ignore_then(byte)
+                .map(Condition::RegisterLess),
+            just("target_dead").to(Condition::RegisterTargetDead),
+            just("even").to(Condition::RegisterEven),
+            just("odd").to(Condition::RegisterOdd),
+            just("flared").to(Condition::HasPostedFlare),
+        ));
+
         // condition parser. returns an Instruction.
         let condition = just("if")
             // the actual condition
             .then(inline_whitespace())
-            .ignore_then(choice((
-                just("alive").to(Condition::VillagerIsAlive),
-                just("dead").to(Condition::VillagerIsDead),
-                just("eq")
-                    .then(inline_whitespace())
-                    .ignore_then(byte)
-                    .map(Condition::RegisterEq),
-            )))
+            .ignore_then(condition_kind)
             // the conditional instructions
-            .then_ignore(whitespace())
+            .then_ignore(padding())
             .then(
                 instructions_block
                     .clone()
                     .delimited_by(just('{'), just('}')),
             )
+            // the optional else instructions
+            .then(
+                padding()
+                    .then(just("else"))
+                    .then(padding())
+                    .ignore_then(
+                        instructions_block
+                            .clone()
+                            .delimited_by(just('{'), just('}')),
+                    )
+                    .or_not(),
+            )
             // construct the instruction
+            .map(
+                |((c, ins), else_ins): ((Condition, Instructions), Option<Instructions>)| {
+                    let ins = Rc::new(ins.into_iter().rev().collect());
+                    match else_ins {
+                        Some(else_ins) => Instruction::ConditionElse(
+                            c,
+                            ins,
+                            Rc::new(else_ins.into_iter().rev().collect()),
+                        ),
+                        None => Instruction::Condition(c, ins),
+                    }
+                },
+            );
+
+        // repeat by register parser. returns an Instruction.
+        let repeat_register = just("repeat")
+            .then(inline_whitespace())
+            .then(just("register"))
+            .then(padding())
+            .ignore_then(
+                instructions_block
+                    .clone()
+                    .delimited_by(just('{'), just('}')),
+            )
+            .map(|ins| Instruction::RepeatRegister(Rc::new(ins.into_iter().rev().collect())));
+
+        // while parser. there's no while instruction, so `while c { ... }` is turned into
+        // `repeat { if c { ... } else { break } }`. returns an Instruction.
+        let while_loop = just("while")
+            .then(inline_whitespace())
+            .ignore_then(condition_kind)
+            .then_ignore(padding())
+            .then(
+                instructions_block
+                    .clone()
+                    .delimited_by(just('{'), just('}')),
+            )
             .map(|(c, ins): (Condition, Instructions)| {
-                Instruction::Condition(c, ins.into_iter().rev().collect())
+                Instruction::Repeat(
+                    u8::MAX,
+                    Rc::new(vec![Instruction::ConditionElse(
+                        c,
+                        Rc::new(ins.into_iter().rev().collect()),
+                        Rc::new(vec![Instruction::Break]),
+                    )]),
+                )
             });
 
-        // repeat parser. returns an Instruction.
+        // repeat parser (with an optional iteration count and label). returns an Instruction.
         let repeat = just("repeat")
-            .then(whitespace())
-            .ignore_then(instructions_block.delimited_by(just('{'), just('}')))
-            .map(|ins| Instruction::Repeat(u8::MAX, ins.into_iter().rev().collect()));
+            .then(padding())
+            .ignore_then(byte.then_ignore(padding()).or_not())
+            .then(loop_label.then_ignore(padding()).or_not())
+            .then(
+                instructions_block
+                    .clone()
+                    .delimited_by(just('{'), just('}')),
+            )
+            .map(|((iterations, label), ins)| {
+                let iterations = iterations.unwrap_or(u8::MAX);
+                let ins = Rc::new(ins.into_iter().rev().collect());
+                match label {
+                    Some(label) => Instruction::LabeledRepeat(label, iterations, ins),
+                    None => Instruction::Repeat(iterations, ins),
+                }
+            });
 
-        // parses a single break
-        let break_instruction = just("break").to(Instruction::Break);
+        // parses a single break, which may be conditional or name the loop to break out of
+        let break_instruction = just("break")
+            .ignore_then(
+                choice((
+                    inline_whitespace()
+                        .then(just("if"))
+                        .then(inline_whitespace())
+                        .ignore_then(condition_kind)
+                        .map(Instruction::BreakIf),
+                    inline_whitespace()
+                        .ignore_then(loop_label)
+                        .map(Instruction::BreakTo),
+                ))
+                .or_not(),
+            )
+            .map(|instruction| instruction.unwrap_or(Instruction::Break));
+
+        // parses a single continue
+        let continue_instruction = just("continue").to(Instruction::Continue);
+
+        // parses a single pass
+        let noop = just("pass").to(Instruction::Noop);
+
+        // parses a block definition, which doesn't produce any instructions itself. the
+        // name is marked as being defined while the body is parsed so it can't call itself
+        let definition = just("define")
+            .then(inline_whitespace())
+            .ignore_then(text::ident::<_, Extra>().map_with(|name: &str, e| {
+                let definitions = e.state();
+                let open_blocks = definitions.open_blocks;
+                definitions.defining.push((name.to_string(), open_blocks));
+                name.to_string()
+            }))
+            .then_ignore(padding())
+            .then(instructions_block.delimited_by(just('{'), just('}')))
+            .map_with(|(name, ins), e| {
+                let definitions = e.state();
+                definitions.defining.pop();
+                let size = instruction_count(&ins);
+                let depth = nesting_depth(&ins);
+                definitions.blocks.insert(
+                    name,
+                    Defined {
+                        instructions: ins,
+                        size,
+                        depth,
+                    },
+                );
+                Vec::new()
+            });
+
+        // parses a call, which is replaced by the instructions of the block it names
+        let call = just("call")
+            .then(inline_whitespace())
+            .ignore_then(text::ident::<_, Extra>())
+            .try_map_with(|name: &str, e| {
+                let span = e.span();
+                let definitions = e.state();
+                if definitions
+                    .defining
+                    .iter()
+                    .any(|(defining, _)| defining == name)
+                {
+                    return Err(Rich::custom(
+                        span,
+                        format!("`{}` can't be called while it's being defined", name),
+                    ));
+                }
+                let block = definitions.blocks.get(name).ok_or_else(|| {
+                    Rich::custom(span, format!("`{}` has not been defined", name))
+                })?;
+
+                definitions.inlined += block.size;
+                if definitions.inlined > MAX_INLINED_INSTRUCTIONS {
+                    return Err(Rich::custom(
+                        span,
+                        format!(
+                            "calls add more than {} instructions",
+                            MAX_INLINED_INSTRUCTIONS
+                        ),
+                    ));
+                }
+
+                // a called block ends up nested inside the blocks around the call,
+                // other than the ones around the define the call is in (if any)
+                let outside = definitions.defining.last().map_or(0, |(_, open)| *open);
+                let nesting = definitions.open_blocks - outside - 1;
+                if nesting + block.depth > definitions.max_depth {
+                    // this is reported once parsing is done, the same way as
+                    // nesting which is written out
+                    definitions.too_deep_at.get_or_insert(span.start);
+                    return Ok(Vec::new());
+                }
+
+                Ok(block.instructions.clone())
+            });
 
         // match as many instructions of any type as possible
-        choice((action, operation, condition, repeat, break_instruction))
-            .padded()
-            .repeated()
-            .collect::<Vec<_>>()
+        let instruction = choice((
+            action,
+            operation,
+            condition,
+            repeat_register,
+            repeat,
+            while_loop,
+            break_instruction,
+            continue_instruction,
+            noop,
+        ));
+
+        let block = choice((
+            instruction.map(|instruction| vec![instruction]),
+            definition,
+            call,
+        ))
+        .then_ignore(padding())
+        .repeated()
+        .collect::<Vec<_>>()
+        .map(|instructions| instructions.into_iter().flatten().collect());
+
+        // keep track of how many blocks are open, for checking calls
+        empty::<&str, Extra>()
+            .map_with(|_, e| e.state().open_blocks += 1)
+            .then(padding().ignore_then(block))
+            .map_with(|(_, instructions), e| {
+                e.state().open_blocks -= 1;
+                instructions
+            })
     })
 }
 
@@ -111,29 +556,332 @@ pub fn parse_instructions(path: PathBuf) -> Result<Instructions, MMParsingError>
     file.read_to_string(&mut buffer)
         .map_err(|_| MMParsingError::BadFile)?;
 
-    // parse the instructions and return on success
-    let parse_result = instructions().parse(&buffer);
+    parse_source(Some(file_name), &buffer, MAX_NESTING_DEPTH)
+}
+
+/// parses mm code from a string rather than a file. nothing is printed if the code is
+/// invalid, so callers can report the error themselves. the returned instructions are in
+/// stack order
+pub fn parse_str(source: &str) -> Result<Instructions, MMParsingError> {
+    parse_source(None, source, MAX_NESTING_DEPTH)
+}
+
+/// like parse_str, but blocks can be nested at most max_depth deep
+#[allow(dead_code)]
+pub fn parse_str_with_max_depth(
+    source: &str,
+    max_depth: usize,
+) -> Result<Instructions, MMParsingError> {
+    parse_source(None, source, max_depth)
+}
+
+/// parses some mm code. if given the name of the file the code came from, any errors
+/// are also printed to the terminal. the returned instructions are in stack order
+fn parse_source(
+    file_name: Option<&str>,
+    buffer: &str,
+    max_depth: usize,
+) -> Result<Instructions, MMParsingError> {
+    // check the nesting before parsing, since that's what could overflow the stack
+    if let Some(offset) = too_deep_at(buffer, max_depth) {
+        let (line, col) = line_and_col(buffer, offset);
+        return Err(MMParsingError::TooDeeplyNested {
+            line,
+            col,
+            max: max_depth,
+        });
+    }
+
+    // keywords aren't case sensitive, but names are
+    let lowercase = lowercase_keywords(buffer);
+
+    // parse the instructions and return on success. anything left over after the
+    // last instruction is an error, rather than being silently ignored
+    let mut definitions = Definitions::new(max_depth);
+    let parse_result = instructions()
+        .then_ignore(end())
+        .parse_with_state(&lowercase, &mut definitions);
+    if let Some(offset) = definitions.too_deep_at {
+        let (line, col) = line_and_col(buffer, offset);
+        return Err(MMParsingError::TooDeeplyNested {
+            line,
+            col,
+            max: max_depth,
+        });
+    }
     if let Some(instructions) = parse_result.output() {
         return Ok(instructions.clone().into_iter().rev().collect());
     }
 
-    // on failure, print all the errors
-    parse_result.errors().for_each(|error| {
-        // again, I chose crates poorly. this error report building is a bit unwieldy.
-        // while it's technically a different crate that does the error reporting,
-        // they're sister projects
-        let span = error.span().start()..error.span().end();
-        let _ = Report::build(ReportKind::Error, (file_name, span.clone()))
-            .with_message(error.to_string())
-            .with_label(
-                Label::new((file_name, span))
-                    .with_color(Color::Red)
-                    .with_message("Parsing failed here"),
-            )
-            .finish()
-            .print((file_name, Source::from(buffer.clone())));
+    // on failure, print all the errors if there's a file to print them for. code
+    // from anywhere else is parsed silently, leaving the caller to report errors
+    let errors = parse_result.errors().collect::<Vec<_>>();
+    if let Some(file_name) = file_name {
+        errors.iter().for_each(|error| {
+            // again, I chose crates poorly. this error report building is a bit unwieldy.
+            // while it's technically a different crate that does the error reporting,
+            // they're sister projects
+            let span = error.span().start()..error.span().end();
+            let _ = Report::build(ReportKind::Error, (file_name, span.clone()))
+                .with_message(error.to_string())
+                .with_label(
+                    Label::new((file_name, span))
+                        .with_color(Color::Red)
+                        .with_message("Parsing failed here"),
+                )
+                .finish()
+                .eprint((file_name, Source::from(buffer)));
+        });
+    }
+
+    // callers get the location of the first error, so they can report it without ariadne
+    let error = errors.first().expect("parsing failed, so there's an error");
+    let (line, col) = line_and_col(buffer, error.span().start());
+    Err(MMParsingError::CannotParse {
+        line,
+        col,
+        message: error.to_string(),
+    })
+}
+
+/// lowercases everything in some mm code other than names (loop labels and the names of
+/// defined blocks), so keywords aren't case sensitive. sticking to ascii keeps every
+/// character the same length, so error spans still line up with the original code
+fn lowercase_keywords(source: &str) -> String {
+    let mut lowercase = String::with_capacity(source.len());
+    let mut chars = source.chars().peekable();
+    let mut name_next = false;
+    while let Some(c) = chars.next() {
+        let is_word = |c: &char| c.is_alphanumeric() || *c == '_';
+
+        if c == '#' {
+            // comments are copied as they are
+            lowercase.push(c);
+            while let Some(c) = chars.next_if(|c| *c != '\n') {
+                lowercase.push(c);
+            }
+        } else if is_word(&c) {
+            let mut word = c.to_string();
+            while let Some(c) = chars.next_if(is_word) {
+                word.push(c);
+            }
+
+            // the word after define or call is a name, and so is kept as it is
+            if name_next {
+                lowercase.push_str(&word);
+                name_next = false;
+            } else {
+                word.make_ascii_lowercase();
+                name_next = word == "define" || word == "call";
+                lowercase.push_str(&word);
+            }
+        } else if c == '\'' {
+            // so is anything straight after a quote
+            lowercase.push(c);
+            while let Some(c) = chars.next_if(is_word) {
+                lowercase.push(c);
+            }
+        } else {
+            // anything but whitespace between a define or call and a name can't parse
+            name_next &= c.is_whitespace();
+            lowercase.push(c);
+        }
+    }
+
+    lowercase
+}
+
+/// finds the byte offset of the first brace which opens a block nested more than
+/// max_depth deep, if there is one. braces in comments are skipped
+fn too_deep_at(source: &str, max_depth: usize) -> Option<usize> {
+    let mut depth = 0usize;
+    let mut in_comment = false;
+    for (offset, c) in source.char_indices() {
+        match c {
+            '\n' => in_comment = false,
+            _ if in_comment => (),
+            '#' => in_comment = true,
+            '{' => {
+                depth += 1;
+                if depth > max_depth {
+                    return Some(offset);
+                }
+            }
+            // unbalanced braces are left for the parser to report
+            '}' => depth = depth.saturating_sub(1),
+            _ => (),
+        }
+    }
+
+    None
+}
+
+/// converts a byte offset in some source into a line and column (both starting at 1)
+fn line_and_col(source: &str, offset: usize) -> (usize, usize) {
+    let before = &source[..offset];
+    let line_start = before.rfind('\n').map(|newline| newline + 1).unwrap_or(0);
+    (
+        before.matches('\n').count() + 1,
+        before[line_start..].chars().count() + 1,
+    )
+}
+
+/// renders instructions (in stack order, like the parser returns them) back into mm code,
+/// one instruction per line with nested blocks indented. parsing the result gives back
+/// the same instructions
+#[allow(dead_code)]
+pub fn to_source(instructions: &Instructions) -> String {
+    let mut source = String::new();
+    write_block(&mut source, instructions, 0);
+    source
+}
+
+/// writes each instruction of a block (in stack order) on its own line
+fn write_block(source: &mut String, instructions: &Instructions, depth: usize) {
+    // blocks are in stack order, so they're written back to front
+    for instruction in instructions.iter().rev() {
+        source.push_str(&"    ".repeat(depth));
+        write_instruction(source, instruction, depth);
+        source.push('\n');
+    }
+}
+
+/// writes a single instruction, with any blocks it has spread over multiple lines
+fn write_instruction(source: &mut String, instruction: &Instruction, depth: usize) {
+    let write_braces = |source: &mut String, block: &Instructions| {
+        if block.is_empty() {
+            source.push_str("{}");
+        } else {
+            source.push_str("{\n");
+            write_block(source, block, depth + 1);
+            source.push_str(&"    ".repeat(depth));
+            source.push('}');
+        }
+    };
+
+    // the default iteration count isn't written in mm code
+    let iterations = |iterations: u8| match iterations {
+        u8::MAX => String::new(),
+        iterations => format!("{} ", iterations),
+    };
+
+    match instruction {
+        Instruction::Condition(condition, block) => {
+            source.push_str(&format!("if {} ", condition));
+            write_braces(source, block);
+        }
+        Instruction::ConditionElse(condition, block, else_block) => {
+            source.push_str(&format!("if {} ", condition));
+            write_braces(source, block);
+            source.push_str(" else ");
+            write_braces(source, else_block);
+        }
+        Instruction::Repeat(count, block) => {
+            source.push_str(&format!("repeat {}", iterations(*count)));
+            write_braces(source, block);
+        }
+        Instruction::LabeledRepeat(label, count, block) => {
+            source.push_str(&format!("repeat {}'{} ", iterations(*count), label));
+            write_braces(source, block);
+        }
+        Instruction::RepeatRegister(block) => {
+            source.push_str("repeat register ");
+            write_braces(source, block);
+        }
+        instruction => source.push_str(&instruction.to_string()),
+    }
+}
+
+/// something in a program which is probably a mistake, but doesn't stop it from running
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+pub enum ParseWarning {
+    /// a conditional or repeat has nothing in its block
+    EmptyBlock,
+    /// instructions come after a break or continue in the same block, so they can never run
+    UnreachableCode,
+    /// a labeled repeat has no `break '` inside it using its label
+    UnusedLabel,
+}
+
+/// successfully parsed instructions (in stack order) along with any warnings about them
+#[derive(Debug, PartialEq, Eq, Clone)]
+pub struct ParseOutcome {
+    pub instructions: Instructions,
+    pub warnings: Vec<ParseWarning>,
+}
+
+/// parses mm code, also collecting warnings about anything that looks like a mistake
+#[allow(dead_code)]
+pub fn parse_str_with_warnings(source: &str) -> Result<ParseOutcome, MMParsingError> {
+    let instructions = parse_str(source)?;
+
+    let mut warnings = Vec::new();
+    lint_block(&instructions, &mut warnings);
+
+    Ok(ParseOutcome {
+        instructions,
+        warnings,
+    })
+}
+
+/// adds warnings about a block (in stack order) and everything nested inside it
+fn lint_block(instructions: &Instructions, warnings: &mut Vec<ParseWarning>) {
+    // blocks are in stack order, so the last instruction written is the first in the
+    // vector. anything written after a jump is therefore before it in the vector
+    if let Some(position) = instructions.iter().position(|instruction| {
+        matches!(
+            instruction,
+            Instruction::Break | Instruction::BreakTo(_) | Instruction::Continue
+        )
+    }) && position != 0
+    {
+        warnings.push(ParseWarning::UnreachableCode);
+    }
+
+    instructions.iter().for_each(|instruction| {
+        if let Instruction::LabeledRepeat(label, _, block) = instruction
+            && !breaks_to(block, label)
+        {
+            warnings.push(ParseWarning::UnusedLabel);
+        }
     });
-    Err(MMParsingError::CannotParse)
+
+    let mut lint_nested = |block: &Instructions| {
+        if block.is_empty() {
+            warnings.push(ParseWarning::EmptyBlock);
+        }
+        lint_block(block, warnings);
+    };
+
+    for instruction in instructions {
+        match instruction {
+            Instruction::Condition(_, block)
+            | Instruction::Repeat(_, block)
+            | Instruction::LabeledRepeat(_, _, block)
+            | Instruction::RepeatRegister(block) => lint_nested(block),
+            Instruction::ConditionElse(_, block, else_block) => {
+                lint_nested(block);
+                lint_nested(else_block);
+            }
+            _ => (),
+        }
+    }
+}
+
+/// whether a block has a break to the provided label anywhere inside it. a nested repeat
+/// with the same label catches any breaks inside it, so those aren't counted
+fn breaks_to(instructions: &Instructions, label: &str) -> bool {
+    instructions.iter().any(|instruction| match instruction {
+        Instruction::BreakTo(target) => target == label,
+        Instruction::LabeledRepeat(inner, _, block) => inner != label && breaks_to(block, label),
+        Instruction::Condition(_, block)
+        | Instruction::Repeat(_, block)
+        | Instruction::RepeatRegister(block) => breaks_to(block, label),
+        Instruction::ConditionElse(_, block, else_block) => {
+            breaks_to(block, label) || breaks_to(else_block, label)
+        }
+        _ => false,
+    })
 }
 
 /// represents anything that can go wrong with parse_instructions()
@@ -145,22 +893,38 @@ pub enum MMParsingError {
     #[error("file is not valid UTF-8")]
     BadFile,
 
-    #[error("invalid code")]
-    CannotParse,
+    #[error("couldn't read mini code from stdin: {0}")]
+    BadStdin(String),
+
+    #[error("no mini code was given")]
+    NoCode,
+
+    #[error("invalid code at line {line}, column {col}: {message}")]
+    CannotParse {
+        line: usize,
+        col: usize,
+        message: String,
+    },
+
+    #[error("blocks are nested more than {max} deep at line {line}, column {col}")]
+    TooDeeplyNested { line: usize, col: usize, max: usize },
 }
 
 #[cfg(test)]
 mod test {
-    use std::u8;
-
     // given how little time I have for this project, I'm not worried about comprehensive
     // tests here. given the declarative nature of the parsing crate I'm using, I'm
     // not too worried about being super comprehensive with my testing
+    use std::rc::Rc;
+
     use chumsky::Parser;
 
     use crate::{
-        mini::{Action, Condition, Instruction, Operation},
-        parser::instructions,
+        mini::{Action, Condition, Instruction, Operation, Register},
+        parser::{
+            MAX_NESTING_DEPTH, MMParsingError, ParseWarning, instructions, parse_str,
+            parse_str_with_max_depth, parse_str_with_warnings, to_source,
+        },
     };
 
     #[test]
@@ -169,7 +933,18 @@ mod test {
             instructions().parse("if eq 8\t{\n\tpost flare\n}").unwrap(),
             vec![Instruction::Condition(
                 Condition::RegisterEq(8),
-                vec![Instruction::Action(Action::PostFlare)]
+                Rc::new(vec![Instruction::Action(Action::PostFlare)])
+            )]
+        )
+    }
+
+    #[test]
+    fn flared() {
+        assert_eq!(
+            parse_str("if flared { post register }").unwrap(),
+            vec![Instruction::Condition(
+                Condition::HasPostedFlare,
+                Rc::new(vec![Instruction::Action(Action::PostRegister)])
             )]
         )
     }
@@ -180,11 +955,36 @@ mod test {
             instructions().parse("repeat\n{\n\t set 15}\t\n").unwrap(),
             vec![Instruction::Repeat(
                 u8::MAX,
-                vec![Instruction::Operation(Operation::SetValue(15))]
+                Rc::new(vec![Instruction::Operation(Operation::SetValue(
+                    Register::A,
+                    15
+                ))])
             )]
         )
     }
 
+    #[test]
+    fn rotations() {
+        assert_eq!(
+            instructions().parse("rotl 3 rotr 1").unwrap(),
+            vec![
+                Instruction::Operation(Operation::RotateLeft(3)),
+                Instruction::Operation(Operation::RotateRight(1))
+            ]
+        )
+    }
+
+    #[test]
+    fn flare_once() {
+        assert_eq!(
+            instructions().parse("post flare once post flare").unwrap(),
+            vec![
+                Instruction::Action(Action::PostFlareOnce),
+                Instruction::Action(Action::PostFlare)
+            ]
+        )
+    }
+
     #[test]
     fn nested() {
         assert_eq!(
@@ -193,11 +993,440 @@ mod test {
                 .unwrap(),
             vec![Instruction::Repeat(
                 u8::MAX,
-                vec![Instruction::Condition(
+                Rc::new(vec![Instruction::Condition(
                     Condition::VillagerIsAlive,
-                    vec![Instruction::Repeat(u8::MAX, vec![Instruction::Break])]
-                )]
+                    Rc::new(vec![Instruction::Repeat(
+                        u8::MAX,
+                        Rc::new(vec![Instruction::Break])
+                    )])
+                )])
+            )]
+        )
+    }
+
+    #[test]
+    fn warnings() {
+        let outcome = parse_str_with_warnings("incr repeat {}").unwrap();
+        assert_eq!(outcome.instructions.len(), 2);
+        assert_eq!(outcome.warnings, vec![ParseWarning::EmptyBlock]);
+
+        let outcome = parse_str_with_warnings("repeat { break incr }").unwrap();
+        assert_eq!(outcome.warnings, vec![ParseWarning::UnreachableCode]);
+
+        let outcome = parse_str_with_warnings("repeat { incr break }").unwrap();
+        assert!(outcome.warnings.is_empty());
+
+        let outcome = parse_str_with_warnings("repeat 'outer { repeat { break 'outer incr } }");
+        assert_eq!(
+            outcome.unwrap().warnings,
+            vec![ParseWarning::UnreachableCode]
+        );
+
+        let outcome = parse_str_with_warnings("repeat { continue incr }").unwrap();
+        assert_eq!(outcome.warnings, vec![ParseWarning::UnreachableCode]);
+
+        let outcome = parse_str_with_warnings("repeat 'outer { incr }").unwrap();
+        assert_eq!(outcome.warnings, vec![ParseWarning::UnusedLabel]);
+
+        // the inner repeat catches the break, so the outer label is never used
+        let outcome = parse_str_with_warnings("repeat 'a { repeat 'a { break 'a } }").unwrap();
+        assert_eq!(outcome.warnings, vec![ParseWarning::UnusedLabel]);
+    }
+
+    #[test]
+    fn repeat_register() {
+        assert_eq!(
+            instructions().parse("repeat register { incr }").unwrap(),
+            vec![Instruction::RepeatRegister(Rc::new(vec![
+                Instruction::Operation(Operation::Increment(Register::A))
+            ]))]
+        )
+    }
+
+    #[test]
+    fn comparisons() {
+        assert_eq!(
+            instructions()
+                .parse("if gt 5 { incr } if lt 5 { decr }")
+                .unwrap(),
+            vec![
+                Instruction::Condition(
+                    Condition::RegisterGreater(5),
+                    Rc::new(vec![Instruction::Operation(Operation::Increment(
+                        Register::A
+                    ))])
+                ),
+                Instruction::Condition(
+                    Condition::RegisterLess(5),
+                    Rc::new(vec![Instruction::Operation(Operation::Decrement(
+                        Register::A
+                    ))])
+                )
+            ]
+        )
+    }
+
+    #[test]
+    fn condition_else() {
+        assert_eq!(
+            instructions()
+                .parse("if alive { incr post register } else { decr }")
+                .unwrap(),
+            vec![Instruction::ConditionElse(
+                Condition::VillagerIsAlive,
+                Rc::new(vec![
+                    Instruction::Action(Action::PostRegister),
+                    Instruction::Operation(Operation::Increment(Register::A))
+                ]),
+                Rc::new(vec![Instruction::Operation(Operation::Decrement(
+                    Register::A
+                ))])
             )]
         )
     }
+
+    #[test]
+    fn repeat_count() {
+        assert_eq!(
+            instructions().parse("repeat 3 { incr }").unwrap(),
+            vec![Instruction::Repeat(
+                3,
+                Rc::new(vec![Instruction::Operation(Operation::Increment(
+                    Register::A
+                ))])
+            )]
+        )
+    }
+
+    #[test]
+    fn registers() {
+        assert_eq!(
+            instructions()
+                .parse("set b 4 incr b decr a swap incr break")
+                .unwrap(),
+            vec![
+                Instruction::Operation(Operation::SetValue(Register::B, 4)),
+                Instruction::Operation(Operation::Increment(Register::B)),
+                Instruction::Operation(Operation::Decrement(Register::A)),
+                Instruction::Operation(Operation::SwapRegisters),
+                Instruction::Operation(Operation::Increment(Register::A)),
+                Instruction::Break
+            ]
+        )
+    }
+
+    #[test]
+    fn break_if() {
+        assert_eq!(
+            instructions()
+                .parse("break if eq 10 break if alive break")
+                .unwrap(),
+            vec![
+                Instruction::BreakIf(Condition::RegisterEq(10)),
+                Instruction::BreakIf(Condition::VillagerIsAlive),
+                Instruction::Break
+            ]
+        )
+    }
+
+    #[test]
+    fn noop() {
+        assert_eq!(
+            instructions().parse("pass incr").unwrap(),
+            vec![
+                Instruction::Noop,
+                Instruction::Operation(Operation::Increment(Register::A))
+            ]
+        )
+    }
+
+    #[test]
+    fn detonate() {
+        assert_eq!(
+            instructions().parse("detonate 4 detonate").unwrap(),
+            vec![
+                Instruction::Action(Action::DetonateAt(4)),
+                Instruction::Action(Action::Detonate)
+            ]
+        )
+    }
+
+    #[test]
+    fn label_conditions() {
+        assert_eq!(
+            instructions()
+                .parse("if alive 4 { incr } if dead { decr }")
+                .unwrap(),
+            vec![
+                Instruction::Condition(
+                    Condition::LabelAlive(4),
+                    Rc::new(vec![Instruction::Operation(Operation::Increment(
+                        Register::A
+                    ))])
+                ),
+                Instruction::Condition(
+                    Condition::VillagerIsDead,
+                    Rc::new(vec![Instruction::Operation(Operation::Decrement(
+                        Register::A
+                    ))])
+                )
+            ]
+        )
+    }
+
+    #[test]
+    fn comments() {
+        assert_eq!(
+            instructions()
+                .parse(
+                    "# a program that only counts\n\n  # (for now)\nincr # bump counter\n# the end"
+                )
+                .unwrap(),
+            vec![Instruction::Operation(Operation::Increment(Register::A))]
+        );
+
+        assert_eq!(
+            instructions()
+                .parse("repeat 2 # twice\n{ # nothing happens\n}")
+                .unwrap(),
+            vec![Instruction::Repeat(2, Rc::new(vec![]))]
+        );
+    }
+
+    #[test]
+    fn error_location() {
+        match parse_str_with_warnings("incr\nbogus") {
+            Err(MMParsingError::CannotParse { line, .. }) => assert_eq!(line, 2),
+            result => panic!("expected a parsing error, got {:?}", result),
+        }
+    }
+
+    #[test]
+    fn trailing_input() {
+        assert!(instructions().parse("incr ???").has_errors());
+        assert!(parse_str_with_warnings("incr ???").is_err());
+    }
+
+    #[test]
+    fn from_str() {
+        // instructions come out in stack order
+        assert_eq!(
+            parse_str("incr\ndecr").unwrap(),
+            vec![
+                Instruction::Operation(Operation::Decrement(Register::A)),
+                Instruction::Operation(Operation::Increment(Register::A)),
+            ]
+        );
+    }
+
+    #[test]
+    fn case_insensitive() {
+        assert_eq!(
+            parse_str("POST FLARE").unwrap(),
+            parse_str("post flare").unwrap()
+        );
+        assert_eq!(
+            parse_str("Repeat { Break }").unwrap(),
+            parse_str("repeat { break }").unwrap()
+        );
+        assert_eq!(
+            parse_str("if ALIVE { incr }").unwrap(),
+            parse_str("if alive { incr }").unwrap()
+        );
+        assert_eq!(
+            parse_str("SET B 12").unwrap(),
+            parse_str("set b 12").unwrap()
+        );
+
+        // names keep their case, so they have to be written the same way every time
+        assert_eq!(
+            parse_str("DEFINE Step { INCR } CALL Step").unwrap(),
+            parse_str("incr").unwrap()
+        );
+        assert!(parse_str("define Step { incr } call step").is_err());
+        let program = parse_str("Repeat 'Outer { Break 'Outer }").unwrap();
+        assert_eq!(
+            program,
+            vec![Instruction::LabeledRepeat(
+                "Outer".to_string(),
+                u8::MAX,
+                Rc::new(vec![Instruction::BreakTo("Outer".to_string())])
+            )]
+        );
+        assert_eq!(parse_str(&to_source(&program)).unwrap(), program);
+        assert_ne!(
+            parse_str("repeat 'Outer { break 'outer }").unwrap(),
+            program
+        );
+    }
+
+    #[test]
+    fn while_loop() {
+        // `while` is just shorthand for a repeat with an if/else inside
+        assert_eq!(
+            parse_str("while alive { post flare incr }").unwrap(),
+            vec![Instruction::Repeat(
+                u8::MAX,
+                Rc::new(vec![Instruction::ConditionElse(
+                    Condition::VillagerIsAlive,
+                    Rc::new(vec![
+                        Instruction::Operation(Operation::Increment(Register::A)),
+                        Instruction::Action(Action::PostFlare),
+                    ]),
+                    Rc::new(vec![Instruction::Break]),
+                )])
+            )]
+        );
+        assert_eq!(
+            parse_str("while eq 3 { incr }").unwrap(),
+            parse_str("repeat { if eq 3 { incr } else { break } }").unwrap()
+        );
+    }
+
+    #[test]
+    fn labeled_loops() {
+        assert_eq!(
+            parse_str("repeat 'outer { repeat 3 'inner { break 'outer } break }").unwrap(),
+            vec![Instruction::LabeledRepeat(
+                "outer".to_string(),
+                u8::MAX,
+                Rc::new(vec![
+                    Instruction::Break,
+                    Instruction::LabeledRepeat(
+                        "inner".to_string(),
+                        3,
+                        Rc::new(vec![Instruction::BreakTo("outer".to_string())])
+                    ),
+                ])
+            )]
+        );
+    }
+
+    #[test]
+    fn define_and_call() {
+        assert_eq!(
+            parse_str("define step { incr post register }\ncall step\nset 7\ncall step").unwrap(),
+            parse_str("incr post register set 7 incr post register").unwrap()
+        );
+
+        // blocks can call blocks defined before them
+        assert_eq!(
+            parse_str("define a { incr } define b { call a call a } call b").unwrap(),
+            parse_str("incr incr").unwrap()
+        );
+
+        // blocks can't call themselves (directly or not), or blocks that don't exist
+        assert!(parse_str("define loop { call loop }").is_err());
+        assert!(parse_str("define a { define b { call a } }").is_err());
+        assert!(parse_str("call nothing").is_err());
+
+        // calling blocks which call other blocks twice doubles the size of the program
+        // each time, which can't go on forever
+        let doubling_chain = |length: usize| {
+            let mut source = "define d0 { incr }".to_string();
+            (1..=length).for_each(|n| {
+                source.push_str(&format!(
+                    "\ndefine d{} {{ call d{} call d{} }}",
+                    n,
+                    n - 1,
+                    n - 1
+                ))
+            });
+            source + &format!("\ncall d{}", length)
+        };
+        assert_eq!(parse_str(&doubling_chain(10)).unwrap().len(), 1024);
+        assert!(matches!(
+            parse_str(&doubling_chain(30)),
+            Err(MMParsingError::CannotParse { message, .. }) if message.contains("calls add more")
+        ));
+    }
+
+    #[test]
+    fn source_round_trip() {
+        let program = parse_str(
+            "set 1 visit repeat 'outer { post register if eq 4 { post flare break 'outer } \
+             else { repeat 2 { incr } } repeat register {} } swap loc",
+        )
+        .unwrap();
+
+        let source = to_source(&program);
+        let expected = [
+            "set 1",
+            "visit",
+            "repeat 'outer {",
+            "    post register",
+            "    if eq 4 {",
+            "        post flare",
+            "        break 'outer",
+            "    } else {",
+            "        repeat 2 {",
+            "            incr",
+            "        }",
+            "    }",
+            "    repeat register {}",
+            "}",
+            "swap loc",
+        ];
+        assert_eq!(source.lines().collect::<Vec<_>>(), expected);
+        assert_eq!(parse_str(&source).unwrap(), program);
+    }
+
+    #[test]
+    fn nesting_depth() {
+        // deeply nested code should fail cleanly instead of overflowing the stack
+        let source = "repeat {".repeat(10_000);
+        assert!(matches!(
+            parse_str(&source),
+            Err(MMParsingError::TooDeeplyNested {
+                line: 1,
+                col,
+                max: MAX_NESTING_DEPTH
+            }) if col == MAX_NESTING_DEPTH * 8 + 8
+        ));
+
+        // code right at the limit is fine (test threads have small stacks, so this
+        // uses a lower limit than the default)
+        let source = format!("{}{}", "repeat {".repeat(8), "}".repeat(8));
+        assert!(parse_str_with_max_depth(&source, 8).is_ok());
+
+        // braces in comments aren't counted
+        let source = "if eq 1 { # {{{\n repeat { incr } }";
+        assert!(parse_str_with_max_depth(source, 2).is_ok());
+        assert!(matches!(
+            parse_str_with_max_depth(source, 1),
+            Err(MMParsingError::TooDeeplyNested { line: 2, .. })
+        ));
+
+        // calls count towards the nesting too, once they're inlined
+        let chain = |length: usize| {
+            let mut source = "define d0 { repeat { incr } }".to_string();
+            (1..length).for_each(|n| {
+                source.push_str(&format!(
+                    "\ndefine d{} {{ repeat {{ call d{} }} }}",
+                    n,
+                    n - 1
+                ))
+            });
+            source + &format!("\ncall d{}", length - 1)
+        };
+        assert!(parse_str(&chain(MAX_NESTING_DEPTH)).is_ok());
+        assert!(matches!(
+            parse_str(&chain(40)),
+            Err(MMParsingError::TooDeeplyNested { line, .. }) if line == MAX_NESTING_DEPTH + 1
+        ));
+        assert!(
+            parse_str_with_max_depth("define a { repeat { incr } } repeat { call a }", 2).is_ok()
+        );
+        assert!(matches!(
+            parse_str_with_max_depth(
+                "define a { repeat { incr } } repeat { repeat { call a } }",
+                2
+            ),
+            Err(MMParsingError::TooDeeplyNested {
+                line: 1,
+                col: 48,
+                max: 2
+            })
+        ));
+    }
 }
@@ -0,0 +1,71 @@
+use serde::Serialize;
+
+use crate::{
+    mini::EventLog,
+    village::{LivingVillager, Village, VillageStatus},
+};
+
+/// everything worth knowing about a game once it's over (or once --code's single day
+/// is over). this is what gets printed with --json
+#[derive(Debug, PartialEq, Eq, Clone, Serialize)]
+pub struct GameSummary {
+    pub seed: u64,
+    pub status: VillageStatus,
+    /// the number of nights which passed
+    pub days: u32,
+    /// the original layout of the village, in order of label
+    pub layout: Vec<LivingVillager>,
+    /// the log of the mini run each day, in order
+    pub mini_logs: Vec<EventLog>,
+}
+
+impl GameSummary {
+    pub fn new(seed: u64, village: &Village, mini_logs: Vec<EventLog>) -> Self {
+        let mut layout = village.layout();
+        layout.sort_by_key(|villager| villager.label());
+
+        Self {
+            seed,
+            status: village.status(),
+            days: village.day(),
+            layout,
+            mini_logs,
+        }
+    }
+
+    pub fn to_json(&self) -> String {
+        serde_json::to_string(self).expect("summaries can always be serialized")
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::{
+        mini::Event,
+        summary::GameSummary,
+        village::{Village, VillageStatus, Villager, VillagerType},
+    };
+
+    #[test]
+    fn json() {
+        let mut village = Village::new_deterministic(vec![
+            Villager::new(VillagerType::Murderer, 1),
+            Villager::new(VillagerType::Normal, 2),
+        ]);
+        while village.status() == VillageStatus::Running {
+            village.run_night();
+        }
+
+        let logs = vec![vec![Event::Visited(2), Event::Finished]];
+        let summary = GameSummary::new(7, &village, logs);
+        let json: serde_json::Value = serde_json::from_str(&summary.to_json()).unwrap();
+
+        assert_eq!(json["seed"], 7);
+        assert_eq!(json["status"], "MurdersWon");
+        assert_eq!(json["days"], village.day());
+        assert_eq!(json["layout"].as_array().unwrap().len(), 2);
+        assert_eq!(json["layout"][0]["label"], 1);
+        assert_eq!(json["mini_logs"][0][0]["Visited"], 2);
+        assert_eq!(json["mini_logs"][0][1], "Finished");
+    }
+}
@@ -1,25 +1,59 @@
-use std::marker::PhantomData;
+use std::{fmt, fs, marker::PhantomData, path::PathBuf};
 
-use rand::{
-    random_bool,
-    seq::SliceRandom,
-};
+use rand::{Rng, SeedableRng, rngs::StdRng, seq::SliceRandom};
 use thiserror::Error;
 
-#[derive(PartialEq, Eq, Clone, Copy)]
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub enum VillagerType {
     Normal,
-    /// strong villagers can survive one attack (if the bool is true, they haven't used their resistance yet)
-    Strong(bool),
+    /// strong villagers can survive attacks (the u8 is how many more attacks they can survive)
+    Strong(u8),
     /// afraid villagers kill minis (but won't delete their logs)
     Afraid,
+    /// each night, doctors revive a random dead villager (murderers excluded)
+    Doctor,
     Murderer,
+    /// each night, poisoners poison a villager the same way murderers pick who to attack.
+    /// poisoned villagers die at the start of the next night. poisoners aren't murderers,
+    /// so the murderers still need to kill them to win
+    Poisoner,
+}
+
+impl VillagerType {
+    /// whether a living villager of this type destroys any mini that visits it
+    pub fn destroys_minis(&self) -> bool {
+        matches!(self, VillagerType::Murderer | VillagerType::Afraid)
+    }
+}
+
+impl fmt::Display for VillagerType {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            VillagerType::Normal => write!(f, "normal villager"),
+            VillagerType::Strong(_) => write!(f, "strong villager"),
+            VillagerType::Afraid => write!(f, "afraid villager"),
+            VillagerType::Doctor => write!(f, "doctor"),
+            VillagerType::Poisoner => write!(f, "poisoner"),
+            VillagerType::Murderer => write!(f, "murderer"),
+        }
+    }
+}
+
+/// what would happen to a mini if it visited a villager
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+pub enum VisitSafety {
+    Safe,
+    /// murderers and afraid villagers destroy visiting minis
+    Destroys,
+    /// dead villagers can be visited, but they don't do anything
+    Dead,
 }
 
 /// villagers have two states: Alive and Dead
-#[derive(Clone, Copy)]
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
 pub enum Alive {}
-#[derive(Clone, Copy)]
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
 pub enum Dead {}
 
 // declare Alive and Dead as villager statuses
@@ -34,10 +68,20 @@ pub type DeadVillager = Villager<Dead>;
 /// lines of not constructing a dead villager and not killing living
 /// villagers and requiring living and dead villagers to be handled
 /// separately, the villager type is tied to the Alive or Dead state.
-#[derive(Clone, Copy)]
+#[derive(Debug, PartialEq, Eq, Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(feature = "serde", serde(bound = ""))]
 pub struct Villager<S: VillagerStatus> {
     kind: VillagerType,
     label: u8,
+    /// only for display. villagers are always identified by their label
+    name: Option<String>,
+    /// if true, the villager dies at the start of the next night. always false for
+    /// dead villagers, since dying (or being revived) cures the poison
+    #[cfg_attr(feature = "serde", serde(default))]
+    poisoned: bool,
+    /// the state is already known from which of the village's lists a villager is saved in
+    #[cfg_attr(feature = "serde", serde(skip))]
     marker: PhantomData<S>,
 }
 
@@ -46,10 +90,16 @@ impl LivingVillager {
         Self {
             kind,
             label,
+            name: None,
+            poisoned: false,
             marker: PhantomData,
         }
     }
 
+    pub fn set_name(&mut self, name: Option<String>) {
+        self.name = name;
+    }
+
     /// only used for village generation. can mess things up if used anywhere else.
     pub fn set_label(&mut self, label: u8) {
         self.label = label;
@@ -60,10 +110,32 @@ impl LivingVillager {
         self.kind = kind;
     }
 
+    pub fn set_poisoned(&mut self, poisoned: bool) {
+        self.poisoned = poisoned;
+    }
+
+    pub fn is_poisoned(&self) -> bool {
+        self.poisoned
+    }
+
     pub fn kill(self) -> Villager<Dead> {
         Villager {
             kind: self.kind,
             label: self.label,
+            name: self.name,
+            poisoned: false,
+            marker: PhantomData,
+        }
+    }
+}
+
+impl DeadVillager {
+    pub fn resurrect(self) -> Villager<Alive> {
+        Villager {
+            kind: self.kind,
+            label: self.label,
+            name: self.name,
+            poisoned: false,
             marker: PhantomData,
         }
     }
@@ -81,18 +153,111 @@ where
         self.label == label
     }
 
+    pub fn name(&self) -> Option<&str> {
+        self.name.as_deref()
+    }
+
     pub fn kind(&self) -> VillagerType {
         self.kind
     }
 }
 
-#[derive(PartialEq, Eq, Clone, Copy)]
+/// a reference to a villager in either state, for looking through a whole village at once
+#[allow(dead_code)]
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+pub enum VillagerRef<'a> {
+    Living(&'a LivingVillager),
+    Dead(&'a DeadVillager),
+}
+
+#[allow(dead_code)]
+impl VillagerRef<'_> {
+    pub fn label(&self) -> u8 {
+        match self {
+            VillagerRef::Living(villager) => villager.label(),
+            VillagerRef::Dead(villager) => villager.label(),
+        }
+    }
+
+    pub fn kind(&self) -> VillagerType {
+        match self {
+            VillagerRef::Living(villager) => villager.kind(),
+            VillagerRef::Dead(villager) => villager.kind(),
+        }
+    }
+}
+
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub enum VillageStatus {
     Running,
     VillagersWon,
     MurdersWon,
+    /// both sides are still alive, but nothing can change from night to night. murderers
+    /// skip over dead villagers and each other, so this only happens when every
+    /// non-murderer left is at label 0 (which generated villages never use)
+    Stalemate,
+    /// the day limit passed before the villagers won
+    TimeExpired,
+}
+
+/// a single murderer's attack during a night
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+pub struct Attack {
+    pub attacker: u8,
+    pub victim: u8,
+    /// if true, the victim was a strong villager who survived by using up one of their lives
+    pub resisted: bool,
 }
 
+/// everything that happened during a night, in the order it happened
+#[derive(Debug, PartialEq, Eq, Clone, Default)]
+pub struct NightReport {
+    /// the labels of the villagers who were poisoned on an earlier night and died
+    pub poison_deaths: Vec<u8>,
+    pub attacks: Vec<Attack>,
+    /// the labels of the villagers poisoned by poisoners (who will die next night)
+    pub poisonings: Vec<u8>,
+    /// the labels of the villagers revived by doctors (doctors act last)
+    pub revivals: Vec<u8>,
+}
+
+/// a direction to look in for villagers, by label
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum Direction {
+    /// towards higher labels
+    Next,
+    /// towards lower labels
+    Prev,
+}
+
+/// something which can be scheduled to happen to the village at the start of a night
+#[allow(dead_code)]
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum VillageEvent {
+    /// changes the type of the living villager with the provided label
+    Convert(u8, VillagerType),
+    Kill(u8),
+    Revive(u8),
+}
+
+/// names given to the villagers of generated villages, by label (starting at 1).
+/// villagers with labels past the end of the list go unnamed
+const NAMES: [&str; 16] = [
+    "Ada", "Basil", "Clara", "Dmitri", "Edith", "Felix", "Greta", "Hugo", "Iris", "Jasper", "Kiri",
+    "Leon", "Mabel", "Nico", "Odette", "Percy",
+];
+
+/// how many villagers' types the player can have revealed each game
+const STARTING_REVEALS: u8 = 1;
+
+/// the most nights a simulation will run before giving up
+const MAX_SIMULATED_NIGHTS: u32 = 1000;
+
+#[derive(Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct Village {
     living_villagers: Vec<LivingVillager>,
     dead_villagers: Vec<DeadVillager>,
@@ -101,40 +266,114 @@ pub struct Village {
     /// the original layout of the village. shown
     /// to the user at the end of the game.
     layout: Vec<LivingVillager>,
+
+    /// all of the village's randomness during nights comes from here. this isn't
+    /// saved, so a loaded village gets a fresh source of randomness
+    #[cfg_attr(feature = "serde", serde(skip, default = "fresh_rng"))]
+    rng: StdRng,
+
+    /// the number of nights which have passed
+    day: u32,
+    /// events to apply at the start of the night ending the paired day
+    scheduled_events: Vec<(u32, VillageEvent)>,
+
+    /// how many more villagers' types can be revealed
+    reveals_left: u8,
+
+    /// if false, the status never leaves Running, leaving it
+    /// to the caller to decide when the game is over
+    auto_terminate: bool,
+
+    /// if true, murderers searching past the highest label wrap around to
+    /// label 1 (and vice versa), as if the village were a ring
+    circular: bool,
+
+    /// the chance of each murderer attacking someone on a given night
+    kill_probability: f64,
+
+    /// if set, the villagers lose once more than this many days have passed
+    day_limit: Option<u32>,
+}
+
+/// a randomly seeded source of randomness for a village
+#[cfg(feature = "serde")]
+fn fresh_rng() -> StdRng {
+    StdRng::from_rng(&mut rand::rng())
 }
 
 impl Village {
     /// constructs a village with the specified number of various types of
-    /// villagers. randomly generates the ordering/labeling of the villagers.
+    /// villagers, where each strong villager can survive `strong_lives` attacks.
+    /// randomly generates the ordering/labeling of the villagers. villagers are labeled
+    /// from 1, so this panics if there are more than 255 of them in total
+    #[allow(dead_code)]
     pub fn new(
         normal_villagers: u8,
         strong_villagers: u8,
+        strong_lives: u8,
+        afraid_villagers: u8,
+        doctors: u8,
+        murderers: u8,
+    ) -> Self {
+        Self::new_seeded(
+            rand::rng().random(),
+            normal_villagers,
+            strong_villagers,
+            strong_lives,
+            afraid_villagers,
+            doctors,
+            murderers,
+        )
+    }
+
+    /// like new, but all of the village's randomness (both the layout and what
+    /// happens each night) is determined by the seed. panics if there are more than 255
+    /// villagers in total
+    pub fn new_seeded(
+        seed: u64,
+        normal_villagers: u8,
+        strong_villagers: u8,
+        strong_lives: u8,
         afraid_villagers: u8,
+        doctors: u8,
         murderers: u8,
     ) -> Self {
         let normal_villagers =
             (0..normal_villagers).map(|_| Villager::new(VillagerType::Normal, 0));
 
         let strong_villagers =
-            (0..strong_villagers).map(|_| Villager::new(VillagerType::Strong(true), 0));
+            (0..strong_villagers).map(|_| Villager::new(VillagerType::Strong(strong_lives), 0));
 
         let afraid_villagers =
             (0..afraid_villagers).map(|_| Villager::new(VillagerType::Afraid, 0));
 
+        let doctors = (0..doctors).map(|_| Villager::new(VillagerType::Doctor, 0));
+
         let murderers = (0..murderers).map(|_| Villager::new(VillagerType::Murderer, 0));
 
         let mut villagers: Vec<LivingVillager> = Vec::new();
         villagers.extend(normal_villagers);
         villagers.extend(strong_villagers);
         villagers.extend(afraid_villagers);
+        villagers.extend(doctors);
         villagers.extend(murderers);
+        assert!(
+            villagers.len() <= u8::MAX as usize,
+            "a village can't have more than {} villagers",
+            u8::MAX
+        );
 
-        let mut rng = rand::rng();
+        let mut rng = StdRng::seed_from_u64(seed);
         let mut ids: Vec<usize> = (1..=villagers.len()).collect();
         ids.shuffle(&mut rng);
 
         villagers.iter_mut().enumerate().for_each(|(i, villager)| {
-            villager.set_label(*ids.get(i).expect("we have enough ids") as u8)
+            let label = *ids.get(i).expect("we have enough ids") as u8;
+            villager.set_label(label);
+            let name = (label as usize)
+                .checked_sub(1)
+                .and_then(|index| NAMES.get(index));
+            villager.set_name(name.map(|name| name.to_string()));
         });
 
         Self {
@@ -142,10 +381,19 @@ impl Village {
             dead_villagers: Vec::new(),
             status: VillageStatus::Running,
             layout: villagers,
+            rng,
+            day: 0,
+            scheduled_events: Vec::new(),
+            reveals_left: STARTING_REVEALS,
+            auto_terminate: true,
+            circular: false,
+            kill_probability: 1.0,
+            day_limit: None,
         }
     }
 
     /// for testing purposes. constructs a village with a pre-determined set of villagers
+    /// (and a fixed seed, so nights are deterministic too)
     #[allow(dead_code)]
     pub fn new_deterministic(villagers: Vec<LivingVillager>) -> Self {
         Self {
@@ -153,9 +401,85 @@ impl Village {
             dead_villagers: Vec::new(),
             status: VillageStatus::Running,
             layout: villagers,
+            rng: StdRng::seed_from_u64(0),
+            day: 0,
+            scheduled_events: Vec::new(),
+            reveals_left: STARTING_REVEALS,
+            auto_terminate: true,
+            circular: false,
+            kill_probability: 1.0,
+            day_limit: None,
         }
     }
 
+    /// saves the village (other than its source of randomness) as json
+    #[cfg(feature = "serde")]
+    #[allow(dead_code)]
+    pub fn to_json(&self) -> String {
+        serde_json::to_string(self).expect("villages can always be serialized")
+    }
+
+    /// loads a village saved by to_json
+    #[cfg(feature = "serde")]
+    #[allow(dead_code)]
+    pub fn from_json(json: &str) -> Result<Self, serde_json::Error> {
+        serde_json::from_str(json)
+    }
+
+    /// constructs a village from a layout file, where each line gives a villager's label and
+    /// type, like `1: murderer` or `2: strong`. blank lines are ignored
+    #[allow(dead_code)]
+    pub fn from_layout_file(path: PathBuf) -> Result<Self, VillageError> {
+        let layout =
+            fs::read_to_string(&path).map_err(|_| VillageError::CannotReadLayout(path.clone()))?;
+        Self::from_layout(&layout)
+    }
+
+    /// constructs a village from the contents of a layout file (see from_layout_file)
+    pub fn from_layout(layout: &str) -> Result<Self, VillageError> {
+        let mut villagers: Vec<LivingVillager> = Vec::new();
+
+        for (number, line) in layout.lines().enumerate() {
+            let line = line.trim();
+            if line.is_empty() {
+                continue;
+            }
+
+            let bad_line = || VillageError::BadLayoutLine(number + 1, line.to_string());
+            let (label, kind) = line.split_once(':').ok_or_else(bad_line)?;
+            let label: u8 = label.trim().parse().map_err(|_| bad_line())?;
+            let kind = match kind.trim() {
+                "normal" => VillagerType::Normal,
+                "strong" => VillagerType::Strong(1),
+                "afraid" => VillagerType::Afraid,
+                "doctor" => VillagerType::Doctor,
+                "murderer" => VillagerType::Murderer,
+                "poisoner" => VillagerType::Poisoner,
+                _ => return Err(bad_line()),
+            };
+
+            if villagers.iter().any(|villager| villager.has_label(label)) {
+                return Err(VillageError::VillagerAlreadyExists(label));
+            }
+            villagers.push(Villager::new(kind, label));
+        }
+
+        let mut village = Self::new_deterministic(villagers);
+        village.reseed(rand::rng().random());
+        Ok(village)
+    }
+
+    /// replaces the village's source of randomness with one seeded by the provided seed
+    pub fn reseed(&mut self, seed: u64) {
+        self.rng = StdRng::seed_from_u64(seed);
+    }
+
+    /// a random number in `0..=max`, taken from the village's source of randomness
+    /// (so reseeding the village makes it deterministic)
+    pub fn random_up_to(&mut self, max: u8) -> u8 {
+        self.rng.random_range(0..=max)
+    }
+
     pub fn layout(&self) -> Vec<LivingVillager> {
         self.layout.clone()
     }
@@ -164,23 +488,152 @@ impl Village {
         self.status
     }
 
-    /// checks if murders or villagers have won. updates status accordingly.
+    /// sets whether the village's status changes once a side has won. turning
+    /// this off is meant for sandboxes, where the game shouldn't end on its own
+    #[allow(dead_code)]
+    pub fn set_auto_terminate(&mut self, auto_terminate: bool) {
+        self.auto_terminate = auto_terminate;
+    }
+
+    /// sets whether murderers treat the village as a ring when searching for victims
+    #[allow(dead_code)]
+    pub fn set_circular(&mut self, circular: bool) {
+        self.circular = circular;
+    }
+
+    /// sets the chance (from 0 to 1) of each murderer attacking someone on a given night.
+    /// panics if the probability isn't between 0 and 1
+    #[allow(dead_code)]
+    pub fn set_kill_probability(&mut self, kill_probability: f64) {
+        assert!(
+            (0.0..=1.0).contains(&kill_probability),
+            "kill probability should be between 0 and 1"
+        );
+        self.kill_probability = kill_probability;
+    }
+
+    /// sets how many days can pass before the villagers lose (None for no limit)
+    pub fn set_day_limit(&mut self, day_limit: Option<u32>) {
+        self.day_limit = day_limit;
+    }
+
+    /// the number of nights which have passed
+    pub fn day(&self) -> u32 {
+        self.day
+    }
+
+    /// schedules an event to happen at the start of the night ending the provided day
+    /// (the first day is day 1). events that no longer make sense by the time they
+    /// happen, like killing a villager who's already dead, are skipped
+    #[allow(dead_code)]
+    pub fn schedule_event(&mut self, day: u32, event: VillageEvent) {
+        self.scheduled_events.push((day, event));
+    }
+
+    fn apply_event(&mut self, event: VillageEvent) -> Result<(), VillageError> {
+        match event {
+            VillageEvent::Convert(label, kind) => self
+                .living_villager_mut(label)
+                .ok_or(VillageError::NoSuchVillager(label))?
+                .set_kind(kind),
+            VillageEvent::Kill(label) => self.kill_villager(label)?,
+            VillageEvent::Revive(label) => self.resurrect_villager(label)?,
+        }
+
+        Ok(())
+    }
+
+    /// checks if murders or villagers have won (or if neither ever can). updates status accordingly.
     pub fn update_status(&mut self) {
-        let murderers = self
-            .living_villagers
-            .iter()
-            .filter(|villager| villager.kind() == VillagerType::Murderer)
-            .count();
+        if !self.auto_terminate {
+            return;
+        }
+
+        let murderers = self.living_murderers();
 
         if murderers == 0 {
             self.status = VillageStatus::VillagersWon;
         } else if murderers == self.living_villagers.len() {
             self.status = VillageStatus::MurdersWon;
+        } else if self.day_limit.is_some_and(|limit| self.day > limit) {
+            self.status = VillageStatus::TimeExpired;
+        } else if self.is_stalemate() {
+            self.status = VillageStatus::Stalemate;
         }
     }
 
-    /// have each murderer attempt to kill a villager and update the village's status
-    pub fn run_night(&mut self) {
+    /// true if no murderer can reach a victim, no doctor has anyone to revive, and
+    /// nothing is scheduled to happen, meaning every future night will go the same way.
+    /// murderers who rarely (or never) go out aren't stuck, since the player's minis can
+    /// still change things. murderers look past dead villagers and each other, so while
+    /// any non-murderer is alive, they're only stuck if the only ones left are at label 0
+    fn is_stalemate(&self) -> bool {
+        let murderers_stuck = self
+            .living_villagers
+            .iter()
+            .filter(|villager| villager.kind() == VillagerType::Murderer)
+            .all(|villager| self.potential_victims(villager.label()) == (None, None));
+
+        let poisoners_stuck = self.living_villagers.iter().all(|villager| {
+            !villager.is_poisoned()
+                && (villager.kind() != VillagerType::Poisoner
+                    || self.potential_victims(villager.label()) == (None, None))
+        });
+
+        let doctors_stuck = !self
+            .living_villagers
+            .iter()
+            .any(|villager| villager.kind() == VillagerType::Doctor)
+            || self
+                .dead_villagers
+                .iter()
+                .all(|villager| villager.kind() == VillagerType::Murderer);
+
+        let nothing_scheduled = self
+            .scheduled_events
+            .iter()
+            .all(|(day, _)| *day <= self.day);
+
+        murderers_stuck && poisoners_stuck && doctors_stuck && nothing_scheduled
+    }
+
+    /// applies the events scheduled for the day that's ending
+    fn apply_scheduled_events(&mut self) {
+        let ending_day = self.day + 1;
+        let events: Vec<VillageEvent> = self
+            .scheduled_events
+            .iter()
+            .filter(|(day, _)| *day == ending_day)
+            .map(|(_, event)| *event)
+            .collect();
+        self.scheduled_events.retain(|(day, _)| *day != ending_day);
+        events.into_iter().for_each(|event| {
+            let _ = self.apply_event(event);
+        });
+    }
+
+    /// have each murderer attempt to kill a villager and update the village's status.
+    /// returns a report of what the murderers did
+    pub fn run_night(&mut self) -> NightReport {
+        self.apply_scheduled_events();
+
+        // villagers poisoned on an earlier night die first. nobody has been poisoned
+        // tonight yet, so everyone poisoned gets a full day before dying
+        let poison_deaths: Vec<u8> = self
+            .living_villagers
+            .iter()
+            .filter(|villager| villager.is_poisoned())
+            .map(|villager| villager.label())
+            .collect();
+        for label in &poison_deaths {
+            self.kill_villager(*label)
+                .expect("the label came from a living villager");
+        }
+        let mut report = NightReport {
+            poison_deaths,
+            ..Default::default()
+        };
+
         // get the labels of all living murderers
         let murderers: Vec<u8> = self
             .living_villagers
@@ -192,26 +645,17 @@ impl Village {
             .collect();
 
         for murder_label in murderers {
-            // get all possible labels of neighbors above and below this murderer.
-            // these are ordered from closes to furthest away from the murderer.
-            let mut neighbors_above = (murder_label + 1)..=u8::MAX;
-            let mut neighbors_below = (1..murder_label).rev();
-
-            // get the label of the nearest living villager above and below the murderer
-            let to_kill_above = neighbors_above.find(|label| {
-                self.living_villager(*label)
-                    .map(|villager| villager.kind() != VillagerType::Murderer)
-                    .unwrap_or(false)
-            });
-            let to_kill_below = neighbors_below.find(|label| {
-                self.living_villager(*label)
-                    .map(|villager| villager.kind() != VillagerType::Murderer)
-                    .unwrap_or(false)
-            });
+            // murderers don't always go out. a probability of 1 never draws from the
+            // rng, so seeded games play out the same as they did before this existed
+            if !self.rng.random_bool(self.kill_probability) {
+                continue;
+            }
+
+            let (to_kill_above, to_kill_below) = self.potential_victims(murder_label);
 
             // randomly pick the villager above or below
             // (even if one is empty/None)
-            let to_kill = match random_bool(0.5) {
+            let to_kill = match self.rng.random_bool(0.5) {
                 true => to_kill_above,
                 false => to_kill_below,
             };
@@ -224,21 +668,315 @@ impl Village {
 
             // kill the villager (note the extra complexity to make sure we
             // properly handle strong villagers)
-            match self
+            let resisted = match self
                 .villager_type(to_kill)
                 .expect("the label came from an existing villager")
             {
-                VillagerType::Strong(true) => self
-                    .living_villager_mut(to_kill)
-                    .expect("the label came from an existing villager")
-                    .set_kind(VillagerType::Strong(false)),
-                _ => self
-                    .kill_villager(to_kill)
-                    .expect("the label came from an existing villager"),
+                VillagerType::Strong(lives) if lives > 0 => {
+                    self.living_villager_mut(to_kill)
+                        .expect("the label came from an existing villager")
+                        .set_kind(VillagerType::Strong(lives - 1));
+                    true
+                }
+                _ => {
+                    self.kill_villager(to_kill)
+                        .expect("the label came from an existing villager");
+                    false
+                }
+            };
+
+            report.attacks.push(Attack {
+                attacker: murder_label,
+                victim: to_kill,
+                resisted,
+            });
+        }
+
+        // each poisoner who survived the murderers poisons someone
+        let poisoners: Vec<u8> = self
+            .living_villagers
+            .iter()
+            .filter(|villager| villager.kind() == VillagerType::Poisoner)
+            .map(|villager| villager.label())
+            .collect();
+        for poisoner_label in poisoners {
+            let (to_poison_above, to_poison_below) = self.potential_victims(poisoner_label);
+            let to_poison = match self.rng.random_bool(0.5) {
+                true => to_poison_above,
+                false => to_poison_below,
+            };
+
+            if let Some(label) = to_poison {
+                self.living_villager_mut(label)
+                    .expect("the label came from a living villager")
+                    .set_poisoned(true);
+                report.poisonings.push(label);
             }
         }
 
+        // each doctor who survived the night revives someone
+        let doctors = self
+            .living_villagers
+            .iter()
+            .filter(|villager| villager.kind() == VillagerType::Doctor)
+            .count();
+        for _ in 0..doctors {
+            let patients: Vec<u8> = self
+                .dead_villagers
+                .iter()
+                .filter(|villager| villager.kind() != VillagerType::Murderer)
+                .map(|villager| villager.label())
+                .collect();
+            if patients.is_empty() {
+                break;
+            }
+
+            let patient = patients[self.rng.random_range(0..patients.len())];
+            self.resurrect_villager(patient)
+                .expect("the label came from a dead villager");
+            report.revivals.push(patient);
+        }
+
+        self.day += 1;
         self.update_status();
+        report
+    }
+
+    /// runs a night exactly as described by a report (along with any scheduled events)
+    /// instead of randomly. fails if the report doesn't make sense for the village, in
+    /// which case the village may have been partially updated
+    #[allow(dead_code)]
+    pub fn apply_report(&mut self, report: &NightReport) -> Result<(), VillageError> {
+        self.apply_scheduled_events();
+
+        for victim in &report.poison_deaths {
+            self.kill_villager(*victim)?;
+        }
+
+        for attack in &report.attacks {
+            if self
+                .living_villager(attack.attacker)
+                .map(|villager| villager.kind())
+                != Some(VillagerType::Murderer)
+            {
+                return Err(VillageError::NotAMurderer(attack.attacker));
+            }
+
+            if attack.resisted {
+                let victim = self
+                    .living_villager_mut(attack.victim)
+                    .ok_or(VillageError::NoSuchVillager(attack.victim))?;
+                match victim.kind() {
+                    VillagerType::Strong(lives) if lives > 0 => {
+                        victim.set_kind(VillagerType::Strong(lives - 1))
+                    }
+                    _ => return Err(VillageError::CannotResist(attack.victim)),
+                }
+            } else {
+                self.kill_villager(attack.victim)?;
+            }
+        }
+
+        for victim in &report.poisonings {
+            self.living_villager_mut(*victim)
+                .ok_or(VillageError::NoSuchVillager(*victim))?
+                .set_poisoned(true);
+        }
+
+        for patient in &report.revivals {
+            self.resurrect_villager(*patient)?;
+        }
+
+        self.day += 1;
+        self.update_status();
+        Ok(())
+    }
+
+    /// runs seeded nights on a copy of the village until the villager with the provided label
+    /// dies, returning how many nights it took. None if the villager isn't alive, or if the
+    /// game ends (or a very large number of nights pass) without it dying
+    #[allow(dead_code)]
+    pub fn expected_nights_to_death(&self, label: u8, seed: u64) -> Option<u32> {
+        self.living_villager(label)?;
+
+        let mut village = self.clone();
+        village.reseed(seed);
+
+        for night in 1..=MAX_SIMULATED_NIGHTS {
+            village.run_night();
+
+            if village.dead_villager(label).is_some() {
+                return Some(night);
+            }
+            if village.status() != VillageStatus::Running {
+                return None;
+            }
+        }
+
+        None
+    }
+
+    /// the label of the nearest living villager in the provided direction from the provided label
+    pub fn nearest_living(&self, label: u8, direction: Direction) -> Option<u8> {
+        match direction {
+            Direction::Next => ((label as u16 + 1)..=u8::MAX as u16)
+                .map(|label| label as u8)
+                .find(|label| self.living_villager(*label).is_some()),
+            Direction::Prev => (0..label)
+                .rev()
+                .find(|label| self.living_villager(*label).is_some()),
+        }
+    }
+
+    /// the labels of the nearest living villagers above and below the provided label
+    /// who pass the filter. in circular villages, the search continues from the other end
+    pub fn neighbors(
+        &self,
+        label: u8,
+        filter: impl Fn(&LivingVillager) -> bool,
+    ) -> (Option<u8>, Option<u8>) {
+        // get all possible labels of neighbors above and below this label.
+        // these are ordered from closest to furthest away from the label.
+        // labels above are found as u16s, since there's nothing above label 255
+        let above = || ((label as u16 + 1)..=u8::MAX as u16).map(|label| label as u8);
+        let mut neighbors_above = above();
+        let mut neighbors_below = (1..label).rev();
+
+        let matches = |label: &u8| self.living_villager(*label).is_some_and(&filter);
+
+        if self.circular {
+            // keep searching from the other end of the village
+            (
+                neighbors_above.chain(1..label).find(matches),
+                neighbors_below.chain(above().rev()).find(matches),
+            )
+        } else {
+            (neighbors_above.find(matches), neighbors_below.find(matches))
+        }
+    }
+
+    /// the label of the living villager at the provided position, counting
+    /// up from zero in order of label
+    pub fn nth_living(&self, index: usize) -> Option<u8> {
+        let mut labels: Vec<u8> = self
+            .living_villagers
+            .iter()
+            .map(|villager| villager.label())
+            .collect();
+        labels.sort();
+
+        labels.get(index).copied()
+    }
+
+    /// the labels of the nearest living non-murderers above and below the provided label.
+    /// these are who a murderer at the label could attack
+    fn potential_victims(&self, label: u8) -> (Option<u8>, Option<u8>) {
+        self.neighbors(label, |villager| villager.kind() != VillagerType::Murderer)
+    }
+
+    /// a rough measure from 0 to 1 of how safe the village is for villagers (higher is safer).
+    ///
+    /// with `v` living non-murderers and `m` living murderers, the score starts at
+    /// `v / (v + m)`. it's then scaled by `1 - e / 2v`, where `e` is the number of
+    /// non-murderers a murderer could attack tonight who don't have a strong villager's
+    /// resistance left. a village without murderers scores 1, and a village
+    /// without non-murderers scores 0
+    pub fn safety_score(&self) -> f64 {
+        let murderers: Vec<u8> = self
+            .living_villagers
+            .iter()
+            .filter(|villager| villager.kind() == VillagerType::Murderer)
+            .map(|villager| villager.label())
+            .collect();
+        let villagers = self.living_villagers.len() - murderers.len();
+
+        if murderers.is_empty() {
+            return 1.0;
+        }
+        if villagers == 0 {
+            return 0.0;
+        }
+
+        let mut exposed: Vec<u8> = murderers
+            .iter()
+            .flat_map(|murderer| {
+                let (above, below) = self.potential_victims(*murderer);
+                [above, below]
+            })
+            .flatten()
+            .filter(|label| !matches!(self.villager_type(*label), Ok(VillagerType::Strong(1..))))
+            .collect();
+        exposed.sort();
+        exposed.dedup();
+
+        let villagers = villagers as f64;
+        let base = villagers / (villagers + murderers.len() as f64);
+        base * (1.0 - exposed.len() as f64 / (2.0 * villagers))
+    }
+
+    /// a summary of the village for the player at the start of a day. doesn't give
+    /// away anything about which villagers are which types
+    pub fn daily_briefing(&self) -> String {
+        let murderers = self.living_murderers();
+
+        let safety = self.safety_score();
+        let safety = if safety >= 0.75 {
+            "safe"
+        } else if safety >= 0.5 {
+            "uneasy"
+        } else {
+            "dangerous"
+        };
+
+        format!(
+            "Day {}\n\
+            {} of {} villagers are alive\n\
+            Forecast: up to {} attack(s) tonight\n\
+            The village feels {}",
+            self.day + 1,
+            self.living_villagers.len(),
+            self.living_villagers.len() + self.dead_villagers.len(),
+            murderers,
+            safety
+        )
+    }
+
+    /// the number of villagers (including murderers) who are still alive
+    pub fn living_count(&self) -> usize {
+        self.living_villagers.len()
+    }
+
+    /// every villager in the village, dead or alive, in order of label
+    #[allow(dead_code)]
+    pub fn iter_all(&self) -> impl Iterator<Item = VillagerRef<'_>> {
+        let mut villagers: Vec<VillagerRef> = self
+            .living_villagers
+            .iter()
+            .map(VillagerRef::Living)
+            .chain(self.dead_villagers.iter().map(VillagerRef::Dead))
+            .collect();
+        villagers.sort_by_key(|villager| villager.label());
+        villagers.into_iter()
+    }
+
+    /// the number of villagers (including murderers) who have died
+    #[allow(dead_code)]
+    pub fn dead_count(&self) -> usize {
+        self.dead_villagers.len()
+    }
+
+    /// the number of villagers (including murderers) in the village, dead or alive
+    #[allow(dead_code)]
+    pub fn total_count(&self) -> usize {
+        self.living_count() + self.dead_count()
+    }
+
+    /// the number of murderers who are still alive
+    pub fn living_murderers(&self) -> usize {
+        self.living_villagers
+            .iter()
+            .filter(|villager| villager.kind() == VillagerType::Murderer)
+            .count()
     }
 
     /// checks if a certain villager exists dead or alive
@@ -252,6 +990,57 @@ impl Village {
                 .any(|villager| villager.has_label(label))
     }
 
+    /// the labels of living villagers whose immediate neighbors (the labels directly
+    /// above and below their own) aren't living villagers, in ascending order
+    #[allow(dead_code)]
+    pub fn isolated_villagers(&self) -> Vec<u8> {
+        let mut isolated: Vec<u8> = self
+            .living_villagers
+            .iter()
+            .map(|villager| villager.label())
+            .filter(|label| {
+                let living_neighbor = |neighbor: Option<u8>| {
+                    neighbor
+                        .map(|neighbor| self.living_villager(neighbor).is_some())
+                        .unwrap_or(false)
+                };
+
+                !living_neighbor(label.checked_sub(1)) && !living_neighbor(label.checked_add(1))
+            })
+            .collect();
+        isolated.sort();
+
+        isolated
+    }
+
+    /// a small set of safe living villagers to start minis at such that, between them,
+    /// every living villager can be reached by repeatedly visiting the nearest living
+    /// villager above or below.
+    ///
+    /// this is a greedy heuristic: ordered by label, the living villagers are split into
+    /// runs of safe villagers separated by villagers who destroy minis, and the lowest
+    /// label of each run is picked. a mini in a run can reach the whole run along with
+    /// the villagers bounding it, so the only villagers missed are those who destroy minis
+    /// and are surrounded by other villagers who destroy minis
+    #[allow(dead_code)]
+    pub fn covering_starts(&self) -> Vec<u8> {
+        let mut living: Vec<&LivingVillager> = self.living_villagers.iter().collect();
+        living.sort_by_key(|villager| villager.label());
+
+        let mut starts = Vec::new();
+        let mut in_run = false;
+        for villager in living {
+            if villager.kind().destroys_minis() {
+                in_run = false;
+            } else if !in_run {
+                starts.push(villager.label());
+                in_run = true;
+            }
+        }
+
+        starts
+    }
+
     /// attempt to get the living villager with the provided label
     pub fn living_villager(&self, label: u8) -> Option<&LivingVillager> {
         self.living_villagers
@@ -288,6 +1077,54 @@ impl Village {
         Ok(kind)
     }
 
+    /// what would happen to a mini visiting the provided label. None if
+    /// there is no such villager (the mini would get lost)
+    pub fn visit_safety(&self, label: u8) -> Option<VisitSafety> {
+        if let Some(villager) = self.living_villager(label) {
+            match villager.kind().destroys_minis() {
+                true => Some(VisitSafety::Destroys),
+                false => Some(VisitSafety::Safe),
+            }
+        } else {
+            self.dead_villager(label).map(|_| VisitSafety::Dead)
+        }
+    }
+
+    /// the visit safety of every villager, dead or alive, ordered by label
+    #[allow(dead_code)]
+    pub fn classify_visit_safety(&self) -> Vec<(u8, VisitSafety)> {
+        let mut labels: Vec<u8> = self
+            .living_villagers
+            .iter()
+            .map(|villager| villager.label())
+            .chain(self.dead_villagers.iter().map(|villager| villager.label()))
+            .collect();
+        labels.sort();
+
+        labels
+            .into_iter()
+            .map(|label| {
+                let safety = self
+                    .visit_safety(label)
+                    .expect("the label came from an existing villager");
+                (label, safety)
+            })
+            .collect()
+    }
+
+    /// uses up one of the village's reveals to get the type of the dead
+    /// or alive villager with the provided label
+    #[allow(dead_code)]
+    pub fn reveal(&mut self, label: u8) -> Result<VillagerType, VillageError> {
+        if self.reveals_left == 0 {
+            return Err(VillageError::NoRevealsLeft);
+        }
+
+        let kind = self.villager_type(label)?;
+        self.reveals_left -= 1;
+        Ok(kind)
+    }
+
     /// attempts to kill the villager with the provided label
     pub fn kill_villager(&mut self, label: u8) -> Result<(), VillageError> {
         let position = self
@@ -300,6 +1137,33 @@ impl Village {
         self.dead_villagers.push(villager.kill());
         Ok(())
     }
+
+    /// adds a new living villager to the village (and its layout)
+    /// at a label no other villager, dead or alive, is using
+    #[allow(dead_code)]
+    pub fn add_villager(&mut self, kind: VillagerType, label: u8) -> Result<(), VillageError> {
+        if self.villager_exists(label) {
+            return Err(VillageError::VillagerAlreadyExists(label));
+        }
+
+        let villager = Villager::new(kind, label);
+        self.living_villagers.push(villager.clone());
+        self.layout.push(villager);
+        Ok(())
+    }
+
+    /// attempts to bring the dead villager with the provided label back to life
+    pub fn resurrect_villager(&mut self, label: u8) -> Result<(), VillageError> {
+        let position = self
+            .dead_villagers
+            .iter()
+            .position(|villager| villager.has_label(label))
+            .ok_or(VillageError::NoSuchVillager(label))?;
+
+        let villager = self.dead_villagers.remove(position);
+        self.living_villagers.push(villager.resurrect());
+        Ok(())
+    }
 }
 
 /// represents anything that can go wrong with village operations.
@@ -309,22 +1173,45 @@ pub enum VillageError {
     /// the u8 represents the label which couldn't be found
     #[error("villager `{0}` in incorrect state or does not exist")]
     NoSuchVillager(u8),
+
+    #[error("villager `{0}` already exists")]
+    VillagerAlreadyExists(u8),
+
+    #[error("villager `{0}` is not a living murderer")]
+    NotAMurderer(u8),
+
+    #[error("villager `{0}` cannot resist an attack")]
+    CannotResist(u8),
+
+    #[error("no reveals left")]
+    NoRevealsLeft,
+
+    #[error("could not read layout file `{0}`")]
+    CannotReadLayout(PathBuf),
+
+    /// the line number (starting at 1) and contents of a line which couldn't be understood
+    #[error("bad layout on line {0}: `{1}`")]
+    BadLayoutLine(usize, String),
 }
 
 #[cfg(test)]
 mod test {
-    use crate::village::{VillageError, VillagerType};
+    use crate::village::{
+        Attack, Direction, LivingVillager, NightReport, VillageError, VillageEvent, VillageStatus,
+        Villager, VillagerRef, VillagerType, VisitSafety,
+    };
 
     use super::Village;
 
     #[test]
     fn correct_villagers_on_creation() {
-        let village = Village::new(5, 4, 3, 2);
+        let village = Village::new(5, 4, 1, 3, 1, 2);
         (1..=9).for_each(|i| assert!(village.living_villager(i).is_some()));
 
         let mut normal_villagers = 0;
         let mut strong_villagers = 0;
         let mut afraid_villagers = 0;
+        let mut doctors = 0;
         let mut murderers = 0;
 
         village
@@ -334,18 +1221,21 @@ mod test {
                 VillagerType::Normal => normal_villagers += 1,
                 VillagerType::Strong(_) => strong_villagers += 1,
                 VillagerType::Afraid => afraid_villagers += 1,
+                VillagerType::Doctor => doctors += 1,
                 VillagerType::Murderer => murderers += 1,
+                VillagerType::Poisoner => unreachable!("villages aren't generated with poisoners"),
             });
 
         assert_eq!(normal_villagers, 5);
         assert_eq!(strong_villagers, 4);
         assert_eq!(afraid_villagers, 3);
+        assert_eq!(doctors, 1);
         assert_eq!(murderers, 2);
     }
 
     #[test]
     fn gets_correct_villagers() {
-        let mut village = Village::new(5, 0, 0, 3);
+        let mut village = Village::new(5, 0, 1, 0, 0, 3);
         village.kill_villager(2).unwrap();
         village.kill_villager(5).unwrap();
 
@@ -370,9 +1260,632 @@ mod test {
 
     #[test]
     fn cannot_kill_villager_twice() {
-        let mut village = Village::new(3, 0, 0, 3);
+        let mut village = Village::new(3, 0, 1, 0, 0, 3);
         assert!(village.kill_villager(2).is_ok());
         assert!(village.kill_villager(4).is_ok());
         assert!(village.kill_villager(2).unwrap_err() == VillageError::NoSuchVillager(2))
     }
+
+    #[test]
+    fn visit_safety() {
+        let mut village = Village::new_deterministic(vec![
+            Villager::new(VillagerType::Normal, 1),
+            Villager::new(VillagerType::Strong(1), 2),
+            Villager::new(VillagerType::Afraid, 3),
+            Villager::new(VillagerType::Murderer, 4),
+            Villager::new(VillagerType::Normal, 5),
+        ]);
+        village.kill_villager(5).unwrap();
+
+        assert_eq!(
+            village.classify_visit_safety(),
+            vec![
+                (1, VisitSafety::Safe),
+                (2, VisitSafety::Safe),
+                (3, VisitSafety::Destroys),
+                (4, VisitSafety::Destroys),
+                (5, VisitSafety::Dead),
+            ]
+        );
+    }
+
+    #[test]
+    fn nights_to_death() {
+        // the murderer can only ever attack the strong villager, which takes two attacks to kill
+        let village = Village::new_deterministic(vec![
+            Villager::new(VillagerType::Murderer, 1),
+            Villager::new(VillagerType::Strong(1), 2),
+        ]);
+
+        let nights = village.expected_nights_to_death(2, 17).unwrap();
+        assert!(nights >= 2);
+        assert_eq!(village.expected_nights_to_death(2, 17), Some(nights));
+
+        // simulating the nights by hand with the same seed should agree
+        let mut simulated = village.clone();
+        simulated.reseed(17);
+        (1..nights).for_each(|_| {
+            simulated.run_night();
+        });
+        assert!(simulated.living_villager(2).is_some());
+        simulated.run_night();
+        assert!(simulated.dead_villager(2).is_some());
+
+        // the original village is untouched, and nonexistent villagers never die
+        assert!(village.living_villager(2).is_some());
+        assert_eq!(village.expected_nights_to_death(3, 17), None);
+    }
+
+    #[test]
+    fn scheduled_events() {
+        let mut village = Village::new_deterministic(vec![
+            Villager::new(VillagerType::Murderer, 1),
+            Villager::new(VillagerType::Normal, 2),
+            Villager::new(VillagerType::Normal, 3),
+            Villager::new(VillagerType::Normal, 4),
+            Villager::new(VillagerType::Normal, 5),
+        ]);
+        village.schedule_event(2, VillageEvent::Convert(5, VillagerType::Murderer));
+
+        village.run_night();
+        assert_eq!(village.day(), 1);
+        assert_eq!(village.villager_type(5), Ok(VillagerType::Normal));
+
+        village.run_night();
+        assert_eq!(village.day(), 2);
+        assert_eq!(village.villager_type(5), Ok(VillagerType::Murderer));
+    }
+
+    #[test]
+    fn isolated_villagers() {
+        let villagers = (1..=6)
+            .map(|i| Villager::new(VillagerType::Normal, i))
+            .collect();
+        let mut village = Village::new_deterministic(villagers);
+        assert!(village.isolated_villagers().is_empty());
+
+        village.kill_villager(2).unwrap();
+        village.kill_villager(4).unwrap();
+        assert_eq!(village.isolated_villagers(), vec![1, 3]);
+    }
+
+    #[test]
+    fn reveals() {
+        let mut village = Village::new_deterministic(vec![
+            Villager::new(VillagerType::Normal, 1),
+            Villager::new(VillagerType::Murderer, 2),
+        ]);
+
+        // failing to find a villager doesn't use up the reveal
+        assert_eq!(village.reveal(3), Err(VillageError::NoSuchVillager(3)));
+        assert_eq!(village.reveal(2), Ok(VillagerType::Murderer));
+        assert_eq!(village.reveal(1), Err(VillageError::NoRevealsLeft));
+    }
+
+    #[test]
+    fn covering_starts() {
+        let mut village = Village::new_deterministic(vec![
+            Villager::new(VillagerType::Normal, 1),
+            Villager::new(VillagerType::Murderer, 2),
+            Villager::new(VillagerType::Normal, 3),
+            Villager::new(VillagerType::Strong(1), 4),
+            Villager::new(VillagerType::Afraid, 5),
+            Villager::new(VillagerType::Normal, 6),
+            Villager::new(VillagerType::Normal, 7),
+            Villager::new(VillagerType::Murderer, 8),
+            Villager::new(VillagerType::Normal, 9),
+        ]);
+        village.kill_villager(7).unwrap();
+
+        let starts = village.covering_starts();
+        assert_eq!(starts, vec![1, 3, 6, 9]);
+
+        // walk up and down from each start, stopping after
+        // the first villager who would destroy the mini
+        let living: Vec<u8> = (1..=9)
+            .filter(|label| village.living_villager(*label).is_some())
+            .collect();
+        let mut reached = Vec::new();
+        for start in starts {
+            let index = living.iter().position(|label| *label == start).unwrap();
+            let up = living[index..].iter();
+            let down = living[..=index].iter().rev();
+
+            for direction in [up.collect::<Vec<_>>(), down.collect()] {
+                for label in direction {
+                    reached.push(*label);
+                    if village.villager_type(*label).unwrap().destroys_minis() {
+                        break;
+                    }
+                }
+            }
+        }
+
+        assert!(living.iter().all(|label| reached.contains(label)));
+    }
+
+    #[test]
+    fn add_villager() {
+        let mut village = Village::new_deterministic(vec![
+            Villager::new(VillagerType::Normal, 1),
+            Villager::new(VillagerType::Normal, 2),
+        ]);
+        village.kill_villager(2).unwrap();
+
+        // labels of dead villagers are still taken
+        assert_eq!(
+            village.add_villager(VillagerType::Afraid, 2),
+            Err(VillageError::VillagerAlreadyExists(2))
+        );
+
+        assert!(village.add_villager(VillagerType::Afraid, 5).is_ok());
+        assert!(village.living_villager(5).is_some());
+        assert_eq!(village.villager_type(5), Ok(VillagerType::Afraid));
+        assert!(
+            village
+                .layout()
+                .iter()
+                .any(|villager| villager.has_label(5))
+        );
+
+        // a murderer added at the highest label can still search for victims
+        assert!(village.add_villager(VillagerType::Murderer, 255).is_ok());
+        village.update_status();
+        village.run_night();
+        assert_eq!(village.day(), 1);
+    }
+
+    #[test]
+    fn safety_score() {
+        let mut village = Village::new_deterministic(vec![
+            Villager::new(VillagerType::Murderer, 1),
+            Villager::new(VillagerType::Strong(0), 2),
+            Villager::new(VillagerType::Normal, 3),
+            Villager::new(VillagerType::Normal, 4),
+            Villager::new(VillagerType::Normal, 5),
+        ]);
+        let start = village.safety_score();
+        assert!(0.0 < start && start < 1.0);
+
+        // the murderer can only attack the strong villager
+        while village.living_villager(2).is_some() {
+            village.run_night();
+        }
+        let after_attack = village.safety_score();
+        assert!(after_attack < start);
+
+        village.kill_villager(1).unwrap();
+        assert!(village.safety_score() > after_attack);
+    }
+
+    #[test]
+    fn nearest_living() {
+        let villagers = (1..=5)
+            .map(|i| Villager::new(VillagerType::Normal, i))
+            .collect();
+        let mut village = Village::new_deterministic(villagers);
+        village.kill_villager(2).unwrap();
+        village.kill_villager(4).unwrap();
+
+        assert_eq!(village.nearest_living(3, Direction::Next), Some(5));
+        assert_eq!(village.nearest_living(3, Direction::Prev), Some(1));
+        assert_eq!(village.nearest_living(5, Direction::Next), None);
+        assert_eq!(village.nearest_living(1, Direction::Prev), None);
+    }
+
+    #[test]
+    fn daily_briefing() {
+        let mut village = Village::new_deterministic(vec![
+            Villager::new(VillagerType::Normal, 1),
+            Villager::new(VillagerType::Normal, 2),
+            Villager::new(VillagerType::Murderer, 3),
+        ]);
+        village.kill_villager(1).unwrap();
+
+        let briefing = village.daily_briefing();
+        assert!(briefing.contains("2 of 3 villagers are alive"));
+        assert!(briefing.contains("Forecast: up to 1 attack(s) tonight"));
+    }
+
+    #[test]
+    fn apply_report() {
+        let mut village = Village::new(4, 2, 1, 0, 1, 2);
+        village.reseed(3);
+        let mut copy = village.clone();
+
+        let report = village.run_night();
+        copy.apply_report(&report).unwrap();
+
+        assert_eq!(village.living_villagers, copy.living_villagers);
+        assert_eq!(village.dead_villagers, copy.dead_villagers);
+        assert_eq!(village.status(), copy.status());
+        assert_eq!(village.day(), copy.day());
+
+        // normal villagers can't resist attacks
+        let mut village = Village::new_deterministic(vec![
+            Villager::new(VillagerType::Murderer, 1),
+            Villager::new(VillagerType::Normal, 2),
+        ]);
+        let report = NightReport {
+            attacks: vec![Attack {
+                attacker: 1,
+                victim: 2,
+                resisted: true,
+            }],
+            ..Default::default()
+        };
+        assert_eq!(
+            village.apply_report(&report),
+            Err(VillageError::CannotResist(2))
+        );
+    }
+
+    #[test]
+    fn no_auto_terminate() {
+        let mut village = Village::new_deterministic(vec![
+            Villager::new(VillagerType::Normal, 1),
+            Villager::new(VillagerType::Normal, 2),
+            Villager::new(VillagerType::Murderer, 3),
+        ]);
+        village.set_auto_terminate(false);
+
+        village.kill_villager(3).unwrap();
+        assert_eq!(village.status(), VillageStatus::Running);
+        assert_eq!(village.living_villagers.len(), 2);
+        assert_eq!(village.dead_villagers.len(), 1);
+
+        // nights still pass without any murderers
+        village.run_night();
+        assert_eq!(village.status(), VillageStatus::Running);
+        assert_eq!(village.living_villagers.len(), 2);
+    }
+
+    #[test]
+    fn doctors() {
+        let mut village = Village::new_deterministic(vec![
+            Villager::new(VillagerType::Doctor, 1),
+            Villager::new(VillagerType::Normal, 2),
+            Villager::new(VillagerType::Normal, 3),
+        ]);
+        village.kill_villager(3).unwrap();
+
+        let report = village.run_night();
+        assert_eq!(report.revivals, vec![3]);
+        assert!(village.living_villager(3).is_some());
+        assert!(village.dead_villagers.is_empty());
+
+        // there's nobody left to revive
+        let report = village.run_night();
+        assert!(report.revivals.is_empty());
+    }
+
+    #[test]
+    fn seeded() {
+        let mut first = Village::new_seeded(42, 5, 2, 1, 2, 1, 2);
+        let mut second = Village::new_seeded(42, 5, 2, 1, 2, 1, 2);
+        assert_eq!(first.layout(), second.layout());
+
+        for _ in 0..3 {
+            assert_eq!(first.run_night(), second.run_night());
+        }
+        assert_eq!(first.living_villagers, second.living_villagers);
+        assert_eq!(first.dead_villagers, second.dead_villagers);
+    }
+
+    #[cfg(feature = "serde")]
+    #[test]
+    fn json_round_trip() {
+        let mut village = Village::new(4, 1, 1, 1, 1, 2);
+        village.kill_villager(3).unwrap();
+
+        let loaded = Village::from_json(&village.to_json()).unwrap();
+        assert_eq!(loaded.living_villagers, village.living_villagers);
+        assert_eq!(loaded.dead_villagers, village.dead_villagers);
+        assert_eq!(loaded.layout(), village.layout());
+        assert_eq!(loaded.status(), village.status());
+
+        assert!(Village::from_json("not a village").is_err());
+    }
+
+    #[test]
+    fn layout_file() {
+        let path = std::env::temp_dir().join("board_game_layout_test.txt");
+        std::fs::write(&path, "1: murderer\n2: strong\n\n3: normal\n").unwrap();
+
+        let village = Village::from_layout_file(path.clone()).unwrap();
+        assert_eq!(village.villager_type(1), Ok(VillagerType::Murderer));
+        assert_eq!(village.villager_type(2), Ok(VillagerType::Strong(1)));
+        assert_eq!(village.villager_type(3), Ok(VillagerType::Normal));
+        assert_eq!(village.living_count(), 3);
+        std::fs::remove_file(&path).unwrap();
+
+        assert_eq!(
+            Village::from_layout("1: murderer\n2 strong").err(),
+            Some(VillageError::BadLayoutLine(2, "2 strong".to_string()))
+        );
+        assert_eq!(
+            Village::from_layout("1: wizard").err(),
+            Some(VillageError::BadLayoutLine(1, "1: wizard".to_string()))
+        );
+        assert_eq!(
+            Village::from_layout("1: normal\n1: afraid").err(),
+            Some(VillageError::VillagerAlreadyExists(1))
+        );
+
+        // the highest label works like any other
+        let mut village = Village::from_layout("254: normal\n255: murderer").unwrap();
+        village.update_status();
+        village.run_night();
+        assert_eq!(village.day(), 1);
+    }
+
+    #[test]
+    fn stalemate() {
+        // murderers only look for victims at labels from 1 up, so nothing can reach the
+        // villager at label 0. this is the only way to get stuck, and generated villages
+        // never use label 0
+        let mut village = Village::new_deterministic(vec![
+            Villager::new(VillagerType::Afraid, 0),
+            Villager::new(VillagerType::Murderer, 1),
+            Villager::new(VillagerType::Normal, 2),
+        ]);
+
+        assert_eq!(village.status(), VillageStatus::Running);
+        while village.living_villager(2).is_some() {
+            village.run_night();
+        }
+
+        assert_eq!(village.living_count(), 2);
+        assert_eq!(village.status(), VillageStatus::Stalemate);
+    }
+
+    #[test]
+    fn day_count() {
+        let mut village = Village::new(3, 1, 1, 1, 1, 1);
+        village.set_auto_terminate(false);
+        assert_eq!(village.day(), 0);
+
+        (0..3).for_each(|_| {
+            village.run_night();
+        });
+        assert_eq!(village.day(), 3);
+    }
+
+    #[test]
+    fn names() {
+        let village = Village::new(6, 2, 1, 2, 0, 2);
+        let layout = village.layout();
+
+        let mut names: Vec<&str> = layout
+            .iter()
+            .map(|villager| villager.name().expect("there are enough names"))
+            .collect();
+        names.sort();
+        names.dedup();
+        assert_eq!(names.len(), 12);
+
+        // villagers are still found by label, not name
+        layout.iter().for_each(|villager| {
+            assert_eq!(village.villager_type(villager.label()), Ok(villager.kind()));
+        });
+        assert_eq!(
+            village.villager_type(13),
+            Err(VillageError::NoSuchVillager(13))
+        );
+    }
+
+    #[test]
+    fn strong_lives() {
+        let mut village = Village::new_deterministic(vec![
+            Villager::new(VillagerType::Strong(2), 1),
+            Villager::new(VillagerType::Murderer, 2),
+        ]);
+
+        // the murderer can only ever attack the strong villager
+        let mut attacked = 0;
+        while attacked < 2 {
+            attacked += village.run_night().attacks.len();
+        }
+        assert_eq!(village.villager_type(1), Ok(VillagerType::Strong(0)));
+        assert_eq!(village.status(), VillageStatus::Running);
+
+        while village.run_night().attacks.is_empty() {}
+        assert!(village.dead_villager(1).is_some());
+        assert_eq!(village.status(), VillageStatus::MurdersWon);
+    }
+
+    #[test]
+    #[should_panic(expected = "a village can't have more than 255 villagers")]
+    fn too_many_villagers() {
+        Village::new_seeded(0, 200, 0, 1, 0, 0, 56);
+    }
+
+    #[test]
+    fn highest_label() {
+        // there's nothing above label 255 to search, whether or not the village wraps around
+        let mut village = Village::new_deterministic(vec![
+            Villager::new(VillagerType::Normal, 254),
+            Villager::new(VillagerType::Murderer, 255),
+        ]);
+        assert_eq!(village.potential_victims(255), (None, Some(254)));
+        village.set_circular(true);
+        assert_eq!(village.potential_victims(255), (Some(254), Some(254)));
+
+        // the biggest village the command line allows uses label 255
+        let mut village = Village::new_seeded(0, 250, 0, 1, 0, 0, 5);
+        assert!(village.villager_exists(255));
+        village.run_night();
+    }
+
+    #[test]
+    fn circular() {
+        let villagers = vec![
+            Villager::new(VillagerType::Normal, 1),
+            Villager::new(VillagerType::Murderer, 2),
+            Villager::new(VillagerType::Murderer, 3),
+        ];
+
+        // without wrapping around, murderer 3 can only find villager 1 by looking down
+        let mut village = Village::new_deterministic(villagers);
+        village.kill_villager(2).unwrap();
+        assert_eq!(village.potential_victims(3), (None, Some(1)));
+
+        // with wrapping around, looking up finds villager 1 too, so they're always attacked
+        village.set_circular(true);
+        assert_eq!(village.potential_victims(3), (Some(1), Some(1)));
+        let report = village.run_night();
+        assert_eq!(report.attacks[0].victim, 1);
+        assert_eq!(village.status(), VillageStatus::MurdersWon);
+    }
+
+    #[test]
+    fn day_limit() {
+        // the strong villager survives every attack, so the game would go on forever
+        let mut village = Village::new_deterministic(vec![
+            Villager::new(VillagerType::Murderer, 1),
+            Villager::new(VillagerType::Strong(10), 2),
+        ]);
+        village.set_day_limit(Some(2));
+
+        village.run_night();
+        village.run_night();
+        assert_eq!(village.status(), VillageStatus::Running);
+        village.run_night();
+        assert_eq!(village.status(), VillageStatus::TimeExpired);
+    }
+
+    #[test]
+    fn kill_probability() {
+        let mut village = Village::new_deterministic(vec![
+            Villager::new(VillagerType::Murderer, 1),
+            Villager::new(VillagerType::Normal, 2),
+            Villager::new(VillagerType::Normal, 3),
+        ]);
+
+        // murderers who never go out never kill anyone, but the game isn't over, since
+        // the player's minis can still reach them
+        village.set_kill_probability(0.0);
+        for _ in 0..20 {
+            assert!(village.run_night().attacks.is_empty());
+        }
+        assert_eq!(village.living_count(), 3);
+        assert_eq!(village.status(), VillageStatus::Running);
+    }
+
+    #[test]
+    fn night_report() {
+        let mut village = Village::new_deterministic(vec![
+            Villager::new(VillagerType::Murderer, 1),
+            Villager::new(VillagerType::Normal, 2),
+            Villager::new(VillagerType::Normal, 3),
+        ]);
+
+        // the murderer only finds someone when looking up
+        let mut report = village.run_night();
+        while report.attacks.is_empty() {
+            report = village.run_night();
+        }
+        assert_eq!(
+            report,
+            NightReport {
+                attacks: vec![Attack {
+                    attacker: 1,
+                    victim: 2,
+                    resisted: false,
+                }],
+                ..Default::default()
+            }
+        );
+    }
+
+    #[test]
+    fn poison() {
+        let mut village = Village::new_deterministic(vec![
+            Villager::new(VillagerType::Poisoner, 1),
+            Villager::new(VillagerType::Normal, 2),
+        ]);
+        village.set_auto_terminate(false);
+
+        // the poisoner only finds someone when looking up
+        while village.run_night().poisonings.is_empty() {}
+        assert!(village.living_villager(2).unwrap().is_poisoned());
+
+        // the poison only takes effect the night after
+        let report = village.run_night();
+        assert_eq!(report.poison_deaths, vec![2]);
+        assert!(report.poisonings.is_empty());
+        assert!(village.dead_villager(2).is_some());
+    }
+
+    #[test]
+    fn neighbors() {
+        let mut village = Village::new_deterministic(
+            (1..=8)
+                .map(|label| Villager::new(VillagerType::Normal, label))
+                .collect(),
+        );
+        [2, 3, 5, 6, 7].iter().for_each(|label| {
+            village.kill_villager(*label).unwrap();
+        });
+
+        let anyone = |_: &LivingVillager| true;
+        assert_eq!(village.neighbors(4, anyone), (Some(8), Some(1)));
+        assert_eq!(village.neighbors(6, anyone), (Some(8), Some(4)));
+        assert_eq!(village.neighbors(1, anyone), (Some(4), None));
+        assert_eq!(village.neighbors(8, anyone), (None, Some(4)));
+
+        // the filter skips over villagers, the same way murderers skip each other at night
+        let not_four = |villager: &LivingVillager| villager.label() != 4;
+        assert_eq!(village.neighbors(6, not_four), (Some(8), Some(1)));
+
+        village.set_circular(true);
+        assert_eq!(village.neighbors(8, anyone), (Some(1), Some(4)));
+    }
+
+    #[test]
+    fn counts() {
+        let mut village = Village::new_deterministic(
+            (1..=6)
+                .map(|label| Villager::new(VillagerType::Normal, label))
+                .collect(),
+        );
+        village.kill_villager(2).unwrap();
+        village.kill_villager(5).unwrap();
+
+        assert_eq!(village.living_count(), 4);
+        assert_eq!(village.dead_count(), 2);
+        assert_eq!(village.total_count(), 6);
+    }
+
+    #[test]
+    fn iter_all() {
+        let mut village = Village::new_deterministic(
+            [4, 1, 3, 2]
+                .iter()
+                .map(|label| Villager::new(VillagerType::Normal, *label))
+                .collect(),
+        );
+        village.kill_villager(3).unwrap();
+        village.kill_villager(1).unwrap();
+
+        let labels: Vec<u8> = village
+            .iter_all()
+            .map(|villager| villager.label())
+            .collect();
+        assert_eq!(labels, vec![1, 2, 3, 4]);
+
+        let dead: Vec<u8> = village
+            .iter_all()
+            .filter(|villager| matches!(villager, VillagerRef::Dead(_)))
+            .map(|villager| villager.label())
+            .collect();
+        assert_eq!(dead, vec![1, 3]);
+    }
+
+    #[test]
+    fn display() {
+        assert_eq!(VillagerType::Normal.to_string(), "normal villager");
+        assert_eq!(VillagerType::Strong(0).to_string(), "strong villager");
+        assert_eq!(VillagerType::Murderer.to_string(), "murderer");
+    }
 }